@@ -1,30 +1,77 @@
 extern crate petgraph;
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::path::Path;
 
-use petgraph::dot::{Config, Dot};
 use petgraph::stable_graph::{EdgeIndex, EdgeReference, NodeIndex, StableDiGraph};
 use petgraph::visit::EdgeRef;
 use petgraph::Direction;
 
+use rustc_data_structures::sync::Lrc;
 use rustc_errors::emitter::{ColorConfig, Emitter, EmitterWriter};
 use rustc_errors::{Diagnostic, Level};
+use syntax::source_map::{FilePathMapping, SourceMap};
+use syntax::util::lev_distance::lev_distance;
+use syntax_pos::{BytePos, Pos, Span};
 
+use crate::annotated::split_top_level;
+use crate::export::{ExportGraph, ExportOptions};
+use crate::paths::PathRemapping;
+use crate::prune::PruneList;
+use crate::query::matches_pattern;
 use crate::summaries::*;
 
-pub type DepGraph = StableDiGraph<String, SourceLocation>;
+// Edges of the dependency graph are call sites and other uses of functions
+#[derive(Clone, Serialize, Debug)]
+pub struct EdgeWeight {
+    pub src_loc: SourceLocation,
+    pub kind: EdgeKind,
+}
+
+pub type DepGraph = StableDiGraph<String, EdgeWeight>;
+
+// Functions allocating on the heap, reported for #[taurus::no_alloc] functions
+const ALLOC_FUNCTIONS: &[&str] = &[
+    "alloc::alloc::*",
+    "alloc::raw_vec::impl::allocate_in",
+    "alloc::raw_vec::impl::with_capacity",
+    "alloc::raw_vec::impl::with_capacity_zeroed",
+    "alloc::raw_vec::impl::reserve",
+    "alloc::raw_vec::impl::reserve_exact",
+    "alloc::raw_vec::impl::reserve_internal",
+    "alloc::raw_vec::impl::try_reserve",
+    "alloc::raw_vec::impl::try_reserve_exact",
+    "alloc::raw_vec::impl::double",
+    "alloc::raw_vec::impl::double_in_place",
+    "alloc::raw_vec::impl::shrink_to_fit",
+    "alloc::boxed::impl::new",
+    "std::alloc::*",
+    "__rust_alloc",
+    "__rust_alloc_zeroed",
+    "__rust_realloc",
+];
 
 pub type ProgPoint = (String, SourceLocation);
 
+#[derive(Serialize)]
+pub struct Hop {
+    pub callee: String,
+    pub src_loc: SourceLocation,
+    pub kind: EdgeKind,
+}
+
+#[derive(Serialize)]
 pub struct DepPath {
-    path: Vec<ProgPoint>,
+    path: Vec<Hop>,
 }
 
 impl DepPath {
-    fn instantiate<'a>(
-        abstract_path: &[EdgeReference<'a, SourceLocation>],
+    pub(crate) fn instantiate<'a>(
+        abstract_path: &[EdgeReference<'a, EdgeWeight>],
         dg: &'a DepGraph,
     ) -> Self {
         DepPath {
@@ -34,71 +81,1050 @@ impl DepPath {
                     let dependent = seg.target();
                     let dependent_name = dg.node_weight(dependent).unwrap();
 
-                    (dependent_name.to_string(), seg.weight().clone())
+                    Hop {
+                        callee: dependent_name.to_string(),
+                        src_loc: seg.weight().src_loc.clone(),
+                        kind: seg.weight().kind,
+                    }
                 })
                 .collect(),
         }
     }
 }
 
+// Identifies a finding independently of the particular path that leads to it
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct FindingKey {
+    pub entry_point: String,
+    pub sink: String,
+    pub category: String,
+}
+
+// Pseudo functions of sensitive operations are named after their sites, which
+// move whenever the code is edited. Findings name them by the kind of the
+// operation and the function containing the site instead, e.g.,
+// `@panic::unwrap in app::run[0]`, such that baselines remain valid
+fn finding_sink(caller: &str, sink: &str) -> String {
+    match sink.find('#') {
+        Some(pos) if sink.starts_with('@') => format!("{} in {}", &sink[..pos], caller),
+        _ => sink.to_string(),
+    }
+}
+
+/// The function containing the site of a pseudo sink named by a finding, or
+/// the sink itself
+pub fn sink_function(sink: &str) -> &str {
+    match sink.find(" in ") {
+        Some(pos) if sink.starts_with('@') => &sink[pos + 4..],
+        _ => sink,
+    }
+}
+
+impl std::fmt::Display for FindingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} reaches {} ({})",
+            self.entry_point, self.sink, self.category
+        )
+    }
+}
+
+pub struct Finding {
+    pub key: FindingKey,
+    // The shortest path found from the entry point to the sink
+    pub witness: DepPath,
+    // Number of other paths found from the entry point to the sink
+    pub alternatives: usize,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "   {}\n", self.key)?;
+        write!(f, "{}", self.witness)?;
+        if self.alternatives > 0 {
+            write!(
+                f,
+                "   ({} alternative paths not shown)\n",
+                self.alternatives
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DepPath {
+    /// The functions called along the path, with the locations and kinds of
+    /// the calls
+    pub fn hops(&self) -> &[Hop] {
+        &self.path
+    }
+}
+
 impl std::fmt::Display for DepPath {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        for pp in &self.path {
-            write!(f, "-> {} at {}\n", pp.0, pp.1)?;
+        for hop in &self.path {
+            // Plain calls are not labeled
+            match hop.kind {
+                EdgeKind::Call => write!(f, "-> {} at {}\n", hop.callee, hop.src_loc)?,
+                kind => write!(f, "-> {} at {} [{}]\n", hop.callee, hop.src_loc, kind)?,
+            }
         }
 
         Ok(())
     }
 }
 
+// An #[taurus::audited] marking that does not discharge any finding, or only
+// findings already covered by an enclosing auditor of the same category or of
+// a parent category
+pub struct UnusedAuditor {
+    pub auditor: ProgPoint,
+    pub category: String,
+    pub enclosing: Option<String>,
+}
+
+// A marking with a category missing from the registry, and the closest known
+// category. Without a registry, sensitive functions of categories no auditor
+// audits are reported instead
+pub struct UnknownCategory {
+    pub item: ProgPoint,
+    pub category: String,
+    pub closest: Option<String>,
+    pub never_audited: bool,
+}
+
 pub struct AuditReport {
-    pub audited: Vec<(String, DepPath)>,
-    pub unaudited: Vec<DepPath>,
+    pub entry_points: Vec<String>,
+    pub audited: Vec<(String, Finding)>,
+    pub unaudited: Vec<Finding>,
+    // Entry points with unaudited [user_check] parameters
+    pub user_check: Vec<(ProgPoint, Vec<String>)>,
+    // Findings of the baseline that are no longer found
+    pub resolved: Vec<FindingKey>,
+    pub unused_auditors: Vec<UnusedAuditor>,
+    // Functions reachable from entry points whose callees were not analyzed,
+    // with the reasons
+    pub pruned: Vec<(String, String)>,
+    // Requested instances of generic entry points missing from the dependency
+    // graph, with the entry points
+    pub uninstantiated: Vec<(String, String)>,
+    pub unknown_categories: Vec<UnknownCategory>,
+}
+
+// Spans of recorded source locations, such that the emitter can render code
+// snippets. The analyzer runs outside of the compiler, so the source files are
+// loaded into a source map of its own
+struct SourceSpans {
+    source_map: Lrc<SourceMap>,
+}
+
+impl SourceSpans {
+    fn new() -> Self {
+        Self {
+            source_map: Lrc::new(SourceMap::new(FilePathMapping::empty())),
+        }
+    }
+
+    fn span(&self, src_loc: &SourceLocation) -> Option<Span> {
+        let file = self.source_map.load_file(Path::new(&src_loc.file)).ok()?;
+        let pos = |line_no: usize, col_no: usize| {
+            let line = file.get_line(line_no.checked_sub(1)?)?;
+            let offset = line
+                .char_indices()
+                .nth(col_no.saturating_sub(1))
+                .map_or(line.len(), |(offset, _)| offset);
+            Some(file.lines[line_no - 1] + BytePos::from_usize(offset))
+        };
+
+        let lo = pos(src_loc.line_no, src_loc.col_no)?;
+        let hi = pos(src_loc.end_line_no, src_loc.end_col_no).unwrap_or(lo);
+        Some(Span::with_root_ctxt(lo, hi))
+    }
+
+    // A diagnostic for a finding, with a code snippet for each call on the
+    // path. Calls whose source is not available are listed without snippets
+    fn finding(&self, level: Level, title: &str, finding: &Finding) -> Diagnostic {
+        let mut diag = Diagnostic::new(level, &format!("{}\n   {}", title, finding.key));
+        for hop in finding.witness.hops() {
+            let label = match hop.kind {
+                EdgeKind::Call => format!("-> {}", hop.callee),
+                kind => format!("-> {} [{}]", hop.callee, kind),
+            };
+            match self.span(&hop.src_loc) {
+                Some(span) => diag.span_note(span, &label),
+                None => diag.note(&format!("{} at {}", label, hop.src_loc)),
+            };
+        }
+        if finding.alternatives > 0 {
+            diag.note(&format!(
+                "{} alternative paths not shown",
+                finding.alternatives
+            ));
+        }
+
+        diag
+    }
+
+    // A diagnostic pointing at an annotated item
+    fn item(&self, level: Level, message: &str, item: &ProgPoint) -> Diagnostic {
+        match self.span(&item.1) {
+            Some(span) => {
+                let mut diag = Diagnostic::new(level, &format!("{}\n   {}", message, item.0));
+                diag.set_span(span);
+                diag
+            }
+            None => Diagnostic::new(level, &format!("{}\n   {} at {}", message, item.0, item.1)),
+        }
+    }
 }
 
 impl AuditReport {
     pub fn emit(&self) {
-        let mut writer = EmitterWriter::stderr(ColorConfig::Auto, None, false, false, None, false);
+        // Spans are interned in the globals of the compiler
+        syntax::with_default_globals(|| self.emit_with_spans(SourceSpans::new()));
+    }
+
+    fn emit_with_spans(&self, spans: SourceSpans) {
+        let mut writer = EmitterWriter::stderr(
+            ColorConfig::Auto,
+            Some(spans.source_map.clone()),
+            false,
+            false,
+            None,
+            false,
+        );
 
         for to_warn in &self.unaudited {
+            let title = if to_warn.key.category == ASM_CATEGORY {
+                "Unaudited inline assembly:"
+            } else if category_ancestors(&to_warn.key.category).contains(&INTRINSIC_CATEGORY) {
+                "Unaudited use of intrinsics:"
+            } else if to_warn.key.category == PANIC_CATEGORY {
+                "Panic reachable from entry point:"
+            } else if to_warn.key.category == GLOBAL_STATE_CATEGORY {
+                "Unaudited access to mutable global state:"
+            } else if to_warn.key.category == ALLOC_CATEGORY {
+                "Heap allocation reachable from no_alloc function:"
+            } else {
+                "Unaudited use of insecure functions:"
+            };
+            writer.emit_diagnostic(&spans.finding(Level::Warning, title, to_warn));
+        }
+
+        for (entry, params) in &self.user_check {
+            let mut diag = spans.item(
+                Level::Warning,
+                "Unaudited [user_check] parameters of entry point:",
+                entry,
+            );
+            diag.note(&params.join(", "));
+            writer.emit_diagnostic(&diag);
+        }
+
+        for unused in &self.unused_auditors {
+            let message = match &unused.enclosing {
+                None => format!(
+                    "Auditor of [{}] does not audit any use of insecure functions:",
+                    unused.category
+                ),
+                Some(enclosing) => format!(
+                    "Redundant auditor of [{}], always called from auditor {}:",
+                    unused.category, enclosing
+                ),
+            };
+            writer.emit_diagnostic(&spans.item(Level::Warning, &message, &unused.auditor));
+        }
+
+        let mut pruned_by_reason = BTreeMap::<&str, Vec<&str>>::new();
+        for (name, reason) in &self.pruned {
+            pruned_by_reason.entry(reason).or_default().push(name);
+        }
+        for (reason, names) in pruned_by_reason {
+            writer.emit_diagnostic(&Diagnostic::new(
+                Level::Note,
+                &format!(
+                    "Callees of {} functions pruned as [{}] were not audited:\n   {}",
+                    names.len(),
+                    reason,
+                    names.join("\n   ")
+                ),
+            ));
+        }
+
+        for (entry, instance) in &self.uninstantiated {
             writer.emit_diagnostic(&Diagnostic::new(
                 Level::Warning,
-                &format!("Unaudited use of insecure functions:\n{}", to_warn),
+                &format!(
+                    "Entry point instance {} is never instantiated:\n   {}",
+                    instance, entry
+                ),
             ));
         }
 
-        for to_note in &self.audited {
+        for unknown in &self.unknown_categories {
+            let title = if unknown.never_audited {
+                format!("Audit category `{}` is never audited:", unknown.category)
+            } else {
+                format!("Unknown audit category `{}`:", unknown.category)
+            };
+            let mut diag = spans.item(Level::Warning, &title, &unknown.item);
+            if let Some(closest) = &unknown.closest {
+                diag.help(&format!("did you mean `{}`?", closest));
+            }
+            writer.emit_diagnostic(&diag);
+        }
+
+        for resolved in &self.resolved {
             writer.emit_diagnostic(&Diagnostic::new(
+                Level::Note,
+                &format!("Finding of the baseline no longer found:\n   {}", resolved),
+            ));
+        }
+
+        for to_note in &self.audited {
+            writer.emit_diagnostic(&spans.finding(
                 Level::Note,
                 &format!(
-                    "Audited use of insecure functions:\n   {}\n{}",
-                    to_note.0, to_note.1
+                    "Audited use of insecure functions, audited by {}:",
+                    to_note.0
                 ),
+                &to_note.1,
             ));
         }
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum PathMode {
+    /// Explore each edge once per entry point and innermost auditor. Only the
+    /// first path found through an edge is reported
+    FirstPerEdge,
+    /// Enumerate simple paths of at most `max_depth` calls, exploring at most
+    /// `max_paths` paths per entry point. Each path ending in a call counts,
+    /// whether or not it reaches a sensitive function
+    AllPaths { max_depth: usize, max_paths: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CategoryMatching {
+    /// Auditors only discharge sensitive functions of the same category
+    Exact,
+    /// Auditors also discharge sensitive functions of subcategories, e.g.,
+    /// `audited("crypto")` discharges `require_audit("crypto::rng")`
+    Hierarchical,
+}
+
+/// The categories containing `category`, from the most specific one to the
+/// least specific one. Subcategories are separated by `::` or `.`
+pub fn category_ancestors(category: &str) -> Vec<&str> {
+    let mut ret = vec![category];
+    let mut rest = category;
+
+    while let Some(pos) = rest.rfind(|c| c == ':' || c == '.') {
+        rest = rest[..pos].trim_end_matches(':');
+        if rest.is_empty() {
+            break;
+        }
+        ret.push(rest);
+    }
+
+    ret
+}
+
+/// Read the categories listed in a manifest, one per line. Lines starting
+/// with `#` are comments
+pub fn load_category_manifest(path: &Path) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read category manifest {}: {}", path.display(), e))?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+fn closest_category<'a>(category: &str, known: &'a BTreeSet<String>) -> Option<&'a String> {
+    let max_distance = std::cmp::max(category.len() / 3, 1);
+    known
+        .iter()
+        .map(|candidate| (lev_distance(category, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+pub struct AuditOptions {
+    pub path_mode: PathMode,
+    pub category_matching: CategoryMatching,
+    // Report panic sites reachable from entry points
+    pub panic_paths: bool,
+    // Kinds of edges followed when searching for paths to sensitive functions
+    pub edge_kinds: HashSet<EdgeKind>,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        Self {
+            path_mode: PathMode::FirstPerEdge,
+            category_matching: CategoryMatching::Exact,
+            panic_paths: false,
+            edge_kinds: EdgeKind::ALL.iter().cloned().collect(),
+        }
+    }
+}
+
+impl AuditOptions {
+    fn follows(&self, edge: EdgeReference<EdgeWeight>) -> bool {
+        self.edge_kinds.contains(&edge.weight().kind)
+    }
+
+    fn reports_category(&self, category: &str) -> bool {
+        category != PANIC_CATEGORY || self.panic_paths
+    }
+
+    // Categories whose auditors discharge sensitive functions of `category`,
+    // from the most specific one
+    fn auditing_categories<'c>(&self, category: &'c str) -> Vec<&'c str> {
+        match self.category_matching {
+            CategoryMatching::Exact => vec![category],
+            CategoryMatching::Hierarchical => category_ancestors(category),
+        }
+    }
+}
+
+pub struct SinkCallers {
+    pub callers: BTreeMap<String, Vec<ProgPoint>>,
+}
+
+impl std::fmt::Display for SinkCallers {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (sink, callers) in &self.callers {
+            write!(f, "{}\n", sink)?;
+            for caller in callers {
+                write!(f, "<- {} at {}\n", caller.0, caller.1)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Paths found from an entry point to a sensitive function
+struct PathGroup {
+    entry: NodeIndex,
+    sink: NodeIndex,
+    shortest: DepPath,
+    count: usize,
+}
+
+fn record_path<K: Ord>(
+    groups: &mut BTreeMap<K, PathGroup>,
+    key: K,
+    entry: NodeIndex,
+    sink: NodeIndex,
+    path: DepPath,
+) {
+    let group = groups.entry(key).or_insert_with(|| PathGroup {
+        entry,
+        sink,
+        shortest: DepPath { path: Vec::new() },
+        count: 0,
+    });
+    if group.count == 0 || path.path.len() < group.shortest.path.len() {
+        group.shortest = path;
+    }
+    group.count += 1;
+}
+
+// State of the depth-first search from an entry point
+struct Traversal<'a> {
+    dg: &'a DepGraph,
+    analyzer: &'a TaurusAnalyzer,
+    options: &'a AuditOptions,
+    // Only report sensitive functions of this category
+    only_category: Option<&'static str>,
+    entry: NodeIndex,
+    // Auditors on the current path for each category, innermost last
+    auditor: HashMap<String, Vec<NodeIndex>>,
+    // Auditors that discharged at least one finding
+    used_auditors: HashSet<NodeIndex>,
+    // Auditors reached with and without an enclosing auditor of the same
+    // category, respectively
+    nested_auditors: HashMap<NodeIndex, NodeIndex>,
+    outermost_auditors: HashSet<NodeIndex>,
+    path: Vec<EdgeReference<'a, EdgeWeight>>,
+    // Auditors of any category on the current path, innermost last
+    auditor_path: Vec<NodeIndex>,
+    // Edges explored under each innermost auditor. An edge reached again
+    // under another auditor is explored again, so that the auditor is not
+    // mistaken for an unused one
+    visited: HashSet<(EdgeIndex, Option<NodeIndex>)>,
+    // Nodes on the current path, used for enumerating simple paths
+    on_path: HashSet<NodeIndex>,
+    // Number of paths explored from the current entry point
+    explored: usize,
+    audited: BTreeMap<(FindingKey, String), PathGroup>,
+    unaudited: BTreeMap<FindingKey, PathGroup>,
+}
+
+impl<'a> Traversal<'a> {
+    fn reports_category(&self, category: &str) -> bool {
+        match self.only_category {
+            Some(only_category) => category == only_category,
+            None => category != ALLOC_CATEGORY && self.options.reports_category(category),
+        }
+    }
+
+    // Auditors on the current path discharging sensitive functions of
+    // `category`, with the ones of the most specific category last
+    fn auditors_of(&self, category: &str) -> Vec<NodeIndex> {
+        let mut ret = Vec::new();
+        for category in self.options.auditing_categories(category).into_iter().rev() {
+            if let Some(auditors) = self.auditor.get(category) {
+                ret.extend(auditors);
+            }
+        }
+        ret
+    }
+
+    // The marking of a function, including built-in sensitive functions
+    fn marking(&self, name: &str) -> Option<Marking> {
+        builtin_marking(name).or_else(|| self.analyzer.get_marking(name))
+    }
+
+    // The innermost auditor on the current path extended to `node`
+    fn innermost_auditor(&self, node: NodeIndex) -> Option<NodeIndex> {
+        let is_auditor = self
+            .analyzer
+            .get_marking(self.dg.node_weight(node).unwrap())
+            .map_or(false, |marking| marking.audited.is_some());
+        if is_auditor {
+            Some(node)
+        } else {
+            self.auditor_path.last().cloned()
+        }
+    }
+
+    fn should_follow(&mut self, edge: EdgeReference<'a, EdgeWeight>) -> bool {
+        if !self.options.follows(edge) {
+            return false;
+        }
+
+        match self.options.path_mode {
+            PathMode::FirstPerEdge => {
+                let innermost = self.innermost_auditor(edge.source());
+                self.visited.insert((edge.id(), innermost))
+            }
+            PathMode::AllPaths {
+                max_depth,
+                max_paths,
+            } => {
+                if self.path.len() < max_depth
+                    && self.explored < max_paths
+                    && !self.on_path.contains(&edge.target())
+                {
+                    self.explored += 1;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    fn traverse(&mut self, current: EdgeReference<'a, EdgeWeight>) {
+        let dg = self.dg;
+        let parent = current.source();
+        let dependent = current.target();
+
+        let parent_name = dg.node_weight(parent).unwrap();
+        let dependent_name = dg.node_weight(dependent).unwrap();
+
+        let parent_audits = self
+            .analyzer
+            .get_marking(parent_name)
+            .and_then(|marking| marking.audited);
+        if let Some(meta) = &parent_audits {
+            self.auditor
+                .entry(meta.to_string())
+                .or_default()
+                .push(parent);
+            self.auditor_path.push(parent);
+        }
+
+        let mut skip_children = false;
+
+        if let Some(marking) = self.marking(dependent_name) {
+            if let Some(meta) = &marking.audited {
+                let enclosing = self
+                    .auditors_of(meta)
+                    .into_iter()
+                    .find(|&idx| idx != dependent);
+                match enclosing {
+                    Some(enclosing) => {
+                        self.nested_auditors.entry(dependent).or_insert(enclosing);
+                    }
+                    None => {
+                        self.outermost_auditors.insert(dependent);
+                    }
+                }
+            }
+            let require_audit = marking
+                .require_audit
+                .as_ref()
+                .filter(|meta| self.reports_category(meta));
+            if let Some(meta) = require_audit {
+                let dep_path = DepPath::instantiate(&self.path, dg);
+                let key = FindingKey {
+                    entry_point: dg.node_weight(self.entry).unwrap().to_string(),
+                    sink: finding_sink(parent_name, dependent_name),
+                    category: meta.to_string(),
+                };
+                let auditors = self.auditors_of(meta);
+                if let Some(&auditor_idx) = auditors.last() {
+                    self.used_auditors.extend(auditors);
+                    let auditor_name = dg.node_weight(auditor_idx).unwrap().to_string();
+                    record_path(
+                        &mut self.audited,
+                        (key, auditor_name),
+                        self.entry,
+                        dependent,
+                        dep_path,
+                    );
+                } else {
+                    record_path(&mut self.unaudited, key, self.entry, dependent, dep_path);
+                    skip_children = true;
+                }
+            }
+        }
+
+        if !skip_children {
+            for edge in dg.edges(dependent) {
+                if self.should_follow(edge) {
+                    self.path.push(edge);
+                    let fresh = self.on_path.insert(edge.target());
+                    self.traverse(edge);
+                    if fresh {
+                        self.on_path.remove(&edge.target());
+                    }
+                    self.path.pop();
+                }
+            }
+        }
+
+        if let Some(meta) = &parent_audits {
+            self.auditor.get_mut(meta).unwrap().pop();
+            self.auditor_path.pop();
+        }
+    }
+
+    // Breadth-first search for the shortest path from `entry` to `sink` that
+    // does not go through any auditor discharging `category`
+    fn shortest_unaudited_path(
+        &self,
+        entry: NodeIndex,
+        sink: NodeIndex,
+        category: &str,
+    ) -> Option<DepPath> {
+        let dg = self.dg;
+        let auditing_categories = self.options.auditing_categories(category);
+        let mut pred = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert(entry);
+        queue.push_back(entry);
+
+        while let Some(node) = queue.pop_front() {
+            if node == sink && node != entry {
+                let mut edges = Vec::new();
+                let mut current = sink;
+                while current != entry {
+                    let edge: EdgeReference<EdgeWeight> = pred[&current];
+                    edges.push(edge);
+                    current = edge.source();
+                }
+                edges.reverse();
+                return Some(DepPath::instantiate(&edges, dg));
+            }
+
+            // Like the depth-first search, do not search past other sensitive
+            // functions reached without an auditor. If this misses the
+            // shortest path, the one found by the depth-first search is used
+            let marking = self.marking(dg.node_weight(node).unwrap());
+            let is_auditor = marking
+                .as_ref()
+                .and_then(|marking| marking.audited.as_ref())
+                .map_or(false, |meta| auditing_categories.contains(&meta.as_str()));
+            let is_sink = node != entry
+                && marking
+                    .as_ref()
+                    .and_then(|marking| marking.require_audit.as_ref())
+                    .map_or(false, |meta| self.reports_category(meta));
+            if is_auditor || is_sink {
+                continue;
+            }
+
+            for edge in dg.edges(node).filter(|&edge| self.options.follows(edge)) {
+                if seen.insert(edge.target()) {
+                    pred.insert(edge.target(), edge);
+                    queue.push_back(edge.target());
+                }
+            }
+        }
+
+        None
+    }
+}
+
 fn without_type_param<'a>(mono_name: &'a str) -> &'a str {
     &mono_name[..mono_name.find('<').unwrap()]
 }
 
+// A pattern for names mangled by the extractor. Paths are matched by their last
+// segment, each segment in the name being followed by its disambiguator
+enum NamePattern {
+    Literal(String),
+    Path(String),
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn after_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.starts_with(prefix) {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+// The length of a leading `[<disambiguator>]`, if there is one
+fn disambiguator_len(name: &str) -> Option<usize> {
+    let rest = after_prefix(name, "[")?;
+    let end = rest.find(']')?;
+    if end > 0 && rest[..end].chars().all(|c| c.is_ascii_digit()) {
+        Some(end + 2)
+    } else {
+        None
+    }
+}
+
+fn matches_name_pattern(name: &str, pattern: &[NamePattern]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((NamePattern::Literal(literal), rest)) => {
+            after_prefix(name, literal).map_or(false, |name| matches_name_pattern(name, rest))
+        }
+        Some((NamePattern::Path(segment), rest)) => {
+            let ident_len = name.find(|c| !is_ident_char(c)).unwrap_or(name.len());
+            if ident_len == 0 {
+                return false;
+            }
+            // Crate names and primitive types have no disambiguator. Type
+            // arguments are separated by `_`, which may also occur in names
+            let len = ident_len + disambiguator_len(&name[ident_len..]).unwrap_or(0);
+            if &name[..ident_len] == segment && matches_name_pattern(&name[len..], rest) {
+                return true;
+            }
+            if let Some(after) = after_prefix(name, segment) {
+                if after.starts_with('_') && matches_name_pattern(after, rest) {
+                    return true;
+                }
+            }
+            // Skip a leading segment of the path
+            match after_prefix(&name[len..], "::") {
+                Some(after) => matches_name_pattern(after, pattern),
+                None => false,
+            }
+        }
+    }
+}
+
+// The pattern of a type argument as mangled by the extractor
+fn type_arg_pattern(arg: &str, ret: &mut Vec<NamePattern>) {
+    let arg = arg.trim();
+    let literal = |s: &str| NamePattern::Literal(s.to_string());
+
+    let prefixes = [
+        ("&mut ", "ref_mut_"),
+        ("&", "ref_"),
+        ("*const ", "pointer_const_"),
+        ("*mut ", "pointer_mut_"),
+    ];
+    for (prefix, mangled) in &prefixes {
+        if let Some(rest) = after_prefix(arg, prefix) {
+            ret.push(literal(mangled));
+            return type_arg_pattern(rest, ret);
+        }
+    }
+
+    if arg.starts_with('[') && arg.ends_with(']') {
+        let inner = &arg[1..arg.len() - 1];
+        match inner.rfind(';') {
+            Some(pos) => {
+                ret.push(literal("array_"));
+                type_arg_pattern(&inner[..pos], ret);
+            }
+            None => {
+                ret.push(literal("slice_"));
+                type_arg_pattern(inner, ret);
+            }
+        }
+    } else if arg.starts_with('(') && arg.ends_with(')') {
+        let elems = split_top_level(&arg[1..arg.len() - 1]);
+        ret.push(NamePattern::Literal(format!("tuple_{}", elems.len())));
+        for elem in elems {
+            ret.push(literal("_"));
+            type_arg_pattern(&elem, ret);
+        }
+    } else if arg == "!" || arg.starts_with("dyn ") || arg.starts_with("fn(") {
+        // Mangled through their debug output, which is not reproduced here
+        ret.push(literal(arg));
+    } else {
+        let (path, args) = match arg.find('<') {
+            Some(pos) if arg.ends_with('>') => {
+                (&arg[..pos], split_top_level(&arg[pos + 1..arg.len() - 1]))
+            }
+            _ => (arg, Vec::new()),
+        };
+        let segment = path.rsplit("::").next().unwrap_or(path);
+        ret.push(NamePattern::Path(segment.to_string()));
+        // Lifetimes are erased
+        for arg in args.iter().filter(|arg| !arg.starts_with('\'')) {
+            ret.push(literal("_"));
+            type_arg_pattern(arg, ret);
+        }
+    }
+}
+
+/// Whether a monomorphized function is the instance of a generic entry point
+/// requested by `#[taurus::entry_point(instances = "...")]`. Each requested
+/// instance is either the type argument of a function with a single type
+/// parameter, such as `Foo<u8>`, or the function name followed by its type
+/// arguments, such as `dispatch<Foo<u8>, u32>`. Paths may omit leading
+/// segments.
+fn matches_entry_instance(mono_name: &str, instance: &str) -> bool {
+    let def_name = without_type_param(mono_name);
+    let type_args = split_top_level(&mono_name[def_name.len() + 1..mono_name.len() - 1]);
+
+    let args = match instance.find('<') {
+        Some(pos) if instance.ends_with('>') => {
+            let name = instance[..pos].trim();
+            let name = name.rsplit("::").next().unwrap_or(name);
+            if matches_name_pattern(def_name, &[NamePattern::Path(name.to_string())]) {
+                split_top_level(&instance[pos + 1..instance.len() - 1])
+            } else {
+                vec![instance.to_string()]
+            }
+        }
+        _ => vec![instance.to_string()],
+    };
+
+    args.len() == type_args.len()
+        && args.iter().zip(&type_args).all(|(arg, type_arg)| {
+            let mut pattern = Vec::new();
+            type_arg_pattern(arg, &mut pattern);
+            matches_name_pattern(type_arg, &pattern)
+        })
+}
+
+// Calls to intrinsics are named `@<category>::<intrinsic>#<site>`
+fn intrinsic_category_of_name(def_name: &str) -> String {
+    let name = &def_name[1..def_name.find('#').unwrap_or_else(|| def_name.len())];
+    name.rsplitn(2, "::")
+        .nth(1)
+        .unwrap_or(INTRINSIC_CATEGORY)
+        .to_string()
+}
+
+// Panic sites, heap allocations, inline assembly and intrinsics are sensitive
+// functions of built-in categories. Only the traversal consults these, such
+// that they do not show up as annotated sinks elsewhere
+fn builtin_marking(name: &str) -> Option<Marking> {
+    let def_name = without_type_param(name);
+    if def_name.starts_with(PANIC_DEF_NAME_CANONICAL) {
+        Some(Marking::sink(PANIC_CATEGORY))
+    } else if def_name.starts_with(ASM_DEF_NAME_CANONICAL) {
+        Some(Marking::sink(ASM_CATEGORY))
+    } else if def_name.starts_with(INTRINSIC_DEF_NAME_CANONICAL) {
+        Some(Marking::sink(&intrinsic_category_of_name(def_name)))
+    } else if ALLOC_FUNCTIONS
+        .iter()
+        .any(|pattern| matches_pattern(def_name, pattern))
+    {
+        Some(Marking::sink(ALLOC_CATEGORY))
+    } else {
+        None
+    }
+}
+
+fn reachable_from(dg: &DepGraph, entry_points: &HashSet<NodeIndex>) -> HashSet<NodeIndex> {
+    let mut reachable = HashSet::new();
+    let mut worklist: Vec<NodeIndex> = entry_points.iter().cloned().collect();
+    while let Some(node) = worklist.pop() {
+        if reachable.insert(node) {
+            worklist.extend(dg.neighbors(node));
+        }
+    }
+    reachable
+}
+
 pub struct TaurusAnalyzer {
     marking_db: PersistentSummaryStore<MarkedItem>,
     calledge_db: PersistentSummaryStore<Vec<DepEdge>>,
+    // Categories declared by each crate
+    category_db: PersistentSummaryStore<Vec<String>>,
+    // Categories listed in the manifest given to the analyzer
+    category_manifest: Vec<String>,
+    // Maps methods in trait impls to the trait methods they implement
+    trait_impls: HashMap<String, String>,
+    // Functions identified as language items by the extractor
+    lang_items: HashSet<String>,
+    prune_list: PruneList,
+    path_remapping: PathRemapping,
 }
 
 impl TaurusAnalyzer {
     pub fn new(db_path: &Path) -> Self {
+        let marking_db = PersistentSummaryStore::<MarkedItem>::new(&db_path.join("marking"))
+            .expect("failed to access consistent storage");
+        let calledge_db = PersistentSummaryStore::<Vec<DepEdge>>::new(&db_path.join("calledge"))
+            .expect("failed to access consistent storage");
+        let category_db = PersistentSummaryStore::<Vec<String>>::new(&db_path.join("category"))
+            .expect("failed to access consistent storage");
+
+        let mut trait_impls = HashMap::new();
+        let mut lang_items = HashSet::new();
+        calledge_db.for_each(|(caller, call_edges)| {
+            for call_edge in call_edges {
+                if call_edge.is_lang_item {
+                    lang_items.insert(caller.clone());
+                }
+                if let Some(trait_item) = call_edge.trait_item {
+                    trait_impls.insert(call_edge.callee_def, trait_item);
+                }
+            }
+        });
+
         Self {
-            marking_db: PersistentSummaryStore::<MarkedItem>::new(&db_path.join("marking"))
-                .expect("failed to access consistent storage"),
-            calledge_db: PersistentSummaryStore::<Vec<DepEdge>>::new(&db_path.join("calledge"))
-                .expect("failed to access consistent storage"),
+            marking_db,
+            calledge_db,
+            category_db,
+            category_manifest: Vec::new(),
+            trait_impls,
+            lang_items,
+            prune_list: PruneList::default(),
+            path_remapping: PathRemapping::default(),
+        }
+    }
+
+    /// Replace the list of functions whose callees are not analyzed
+    pub fn set_prune_list(&mut self, prune_list: PruneList) {
+        self.prune_list = prune_list;
+    }
+
+    pub fn set_category_manifest(&mut self, category_manifest: Vec<String>) {
+        self.category_manifest = category_manifest;
+    }
+
+    pub fn set_path_remapping(&mut self, path_remapping: PathRemapping) {
+        self.path_remapping = path_remapping;
+    }
+
+    fn remap(&self, mut src_loc: SourceLocation) -> SourceLocation {
+        src_loc.file = self.path_remapping.remap(&src_loc.file);
+        src_loc
+    }
+
+    // The name of the item whose marking selected by `select` applies to a
+    // (monomorphized) function. It is either the function itself or the trait
+    // method implemented by the function
+    fn marking_origin<T>(
+        &self,
+        name: &str,
+        select: impl Fn(Marking) -> Option<T>,
+    ) -> Option<String> {
+        let def_name = without_type_param(name);
+        let is_marked = |def_name: &str| {
+            self.marking_db
+                .get(def_name)
+                .map_or(false, |item| select(item.marking).is_some())
+        };
+
+        if is_marked(def_name) {
+            Some(def_name.to_string())
+        } else {
+            self.trait_impls
+                .get(def_name)
+                .filter(|trait_item| is_marked(trait_item))
+                .cloned()
+        }
+    }
+
+    /// The name of the item whose #[taurus::require_audit] marking applies to
+    /// a (monomorphized) function. It is either the function itself or the
+    /// trait method implemented by the function.
+    pub fn sink_origin(&self, name: &str) -> Option<String> {
+        self.marking_origin(name, |marking| marking.require_audit)
+    }
+
+    /// The name of the item whose #[taurus::audited] marking applies to a
+    /// (monomorphized) function
+    pub fn auditor_origin(&self, name: &str) -> Option<String> {
+        self.marking_origin(name, |marking| marking.audited)
+    }
+
+    pub fn is_lang_item(&self, name: &str) -> bool {
+        self.lang_items.contains(name)
+    }
+
+    /// All marked items in the depstore, indexed by their names
+    pub fn markings(&self) -> BTreeMap<String, MarkedItem> {
+        let mut ret = BTreeMap::new();
+        self.marking_db.for_each(|(name, marked_item)| {
+            ret.insert(name, marked_item);
+        });
+        ret
+    }
+
+    /// Look up the marking of a (monomorphized) function. Markings on trait
+    /// methods apply to all implementations of the methods, in any crate.
+    /// Markings on the implementation itself take precedence.
+    pub fn get_marking(&self, name: &str) -> Option<Marking> {
+        let def_name = without_type_param(name);
+        let own = self.marking_db.get(def_name).map(|item| item.marking);
+        let inherited = self
+            .trait_impls
+            .get(def_name)
+            .and_then(|trait_item| self.marking_db.get(trait_item))
+            .map(|item| item.marking);
+
+        match (own, inherited) {
+            (Some(own), Some(inherited)) => Some(Marking {
+                require_audit: own.require_audit.or(inherited.require_audit),
+                audited: own.audited.or(inherited.audited),
+                is_entry_point: own.is_entry_point,
+                entry_instances: own.entry_instances,
+                user_check_params: own.user_check_params,
+                no_alloc: own.no_alloc || inherited.no_alloc,
+            }),
+            (own, inherited) => own.or(inherited),
         }
     }
 
     pub fn get_depgraph(&self) -> (DepGraph, HashSet<NodeIndex>) {
+        let (dg, entry_points, _) = self.build_depgraph();
+        (dg, entry_points)
+    }
+
+    // Build the dependency graph, also returning the functions whose outgoing
+    // edges were pruned and the reasons
+    fn build_depgraph(&self) -> (DepGraph, HashSet<NodeIndex>, Vec<(NodeIndex, String)>) {
         let db_size = self.calledge_db.len();
         let mut ret = DepGraph::with_capacity(db_size, 2 * db_size);
         let mut nodeidx = HashMap::<String, NodeIndex>::new();
@@ -112,62 +1138,51 @@ impl TaurusAnalyzer {
             }
         };
 
-        // construct the graph and record language items that should be pruned
-        let mut lang_items = HashSet::<NodeIndex>::new();
+        // construct the graph and record functions whose callees should be pruned
+        let mut pruned = Vec::new();
 
         self.calledge_db.for_each(|(caller, call_edges)| {
             let caller_idx = get_nodeidx(&mut ret, &caller);
-            for call_edge in call_edges {
-                let callee_idx = get_nodeidx(&mut ret, &call_edge.full_callee_name());
-                ret.add_edge(caller_idx, callee_idx, call_edge.src_loc);
-                if call_edge.is_lang_item {
-                    lang_items.insert(caller_idx);
-                }
-            }
-        });
-
-        // prune edges (and dangling nodes) reached from language items using bfs
-        let mut edges_to_prune = HashSet::<EdgeIndex>::new();
-        let mut worklist: Vec<NodeIndex> = lang_items.iter().map(|x| *x).collect();
-        let mut nodes_to_prune = lang_items;
-
-        while worklist.len() > 0 {
-            let mut nodes_to_inspect = HashSet::<NodeIndex>::new();
-            for node in &worklist {
-                for out_edge in ret.edges(*node) {
-                    nodes_to_inspect.insert(out_edge.target());
-                    edges_to_prune.insert(out_edge.id());
+            let reason = self
+                .prune_list
+                .reason(&caller, call_edges.iter().any(|edge| edge.is_lang_item));
+            match reason {
+                // Only calls made by the pruned function are removed. Its
+                // callees remain reachable through their other callers
+                Some(reason) if !call_edges.is_empty() => {
+                    pruned.push((caller_idx, reason.to_string()));
                 }
-            }
-            worklist.clear();
-            for prune_candidate in nodes_to_inspect {
-                if ret
-                    .edges_directed(prune_candidate, Direction::Incoming)
-                    .all(|edge_ref| edges_to_prune.contains(&edge_ref.id()))
-                {
-                    if nodes_to_prune.insert(prune_candidate) {
-                        worklist.push(prune_candidate);
+                _ => {
+                    for call_edge in call_edges {
+                        let callee_idx = get_nodeidx(&mut ret, &call_edge.full_callee_name());
+                        ret.add_edge(
+                            caller_idx,
+                            callee_idx,
+                            EdgeWeight {
+                                src_loc: self.remap(call_edge.src_loc),
+                                kind: call_edge.kind,
+                            },
+                        );
                     }
                 }
             }
-        }
-
-        for edge in edges_to_prune {
-            ret.remove_edge(edge);
-        }
-
-        for node in nodes_to_prune {
-            ret.remove_node(node);
-        }
+        });
 
         let entry_points: HashSet<NodeIndex> = ret
             .node_indices()
             .filter(|&node_idx| {
-                let key = &ret.node_weight(node_idx).unwrap();
+                let key = ret.node_weight(node_idx).unwrap();
                 self.marking_db
-                    .get(without_type_param(&key))
+                    .get(without_type_param(key))
                     .and_then(|marked_item| {
-                        if marked_item.marking.is_entry_point {
+                        let marking = marked_item.marking;
+                        if marking.is_entry_point
+                            && marking.entry_instances.map_or(true, |instances| {
+                                instances
+                                    .iter()
+                                    .any(|instance| matches_entry_instance(key, instance))
+                            })
+                        {
                             Some(())
                         } else {
                             None
@@ -179,105 +1194,409 @@ impl TaurusAnalyzer {
 
         debug!("found {} entry points", entry_points.len());
 
-        (ret, entry_points)
+        (ret, entry_points, pruned)
     }
 
-    pub fn audit(&self) -> AuditReport {
+    pub fn audit(&self, options: &AuditOptions) -> AuditReport {
+        let (dg, entry_points, pruned) = self.build_depgraph();
+        let mut report = self.audit_from(&dg, &entry_points, pruned, options, None);
+
+        // Generic entry points may be instantiated in any crate, so requested
+        // instances are only known to be missing once all crates are analyzed
+        for (name, marked_item) in self.markings() {
+            for instance in marked_item.marking.entry_instances.unwrap_or_default() {
+                let instantiated = dg.node_indices().any(|idx| {
+                    let mono_name = dg.node_weight(idx).unwrap();
+                    without_type_param(mono_name) == name
+                        && matches_entry_instance(mono_name, &instance)
+                });
+                if !instantiated {
+                    report.uninstantiated.push((name.clone(), instance));
+                }
+            }
+        }
+
+        report.unknown_categories = self.unknown_categories(options);
+
+        // Pointers passed to [user_check] parameters of ECALLs are not checked
+        // by the generated bridge. The ECALL itself must be audited
+        for &entry in &entry_points {
+            let entry_name = dg.node_weight(entry).unwrap();
+            if let Some(marked_item) = self.marking_db.get(without_type_param(entry_name)) {
+                let marking = marked_item.marking;
+                if !marking.user_check_params.is_empty()
+                    && marking.audited.as_ref().map(String::as_str) != Some(USER_CHECK_CATEGORY)
+                {
+                    report.user_check.push((
+                        (entry_name.to_string(), self.remap(marked_item.src_loc)),
+                        marking.user_check_params,
+                    ));
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Report heap allocations reachable from #[taurus::no_alloc] functions,
+    /// which take the place of entry points in the report
+    pub fn audit_allocations(&self, options: &AuditOptions) -> AuditReport {
+        let (dg, _, pruned) = self.build_depgraph();
+        let roots: HashSet<NodeIndex> = dg
+            .node_indices()
+            .filter(|&idx| {
+                self.get_marking(dg.node_weight(idx).unwrap())
+                    .map_or(false, |marking| marking.no_alloc)
+            })
+            .collect();
+
+        self.audit_from(&dg, &roots, pruned, options, Some(ALLOC_CATEGORY))
+    }
+
+    fn audit_from(
+        &self,
+        dg: &DepGraph,
+        entry_points: &HashSet<NodeIndex>,
+        pruned: Vec<(NodeIndex, String)>,
+        options: &AuditOptions,
+        only_category: Option<&'static str>,
+    ) -> AuditReport {
         let mut report = AuditReport {
+            entry_points: entry_points
+                .iter()
+                .map(|&idx| dg.node_weight(idx).unwrap().to_string())
+                .collect(),
             audited: Vec::new(),
             unaudited: Vec::new(),
+            user_check: Vec::new(),
+            resolved: Vec::new(),
+            unused_auditors: Vec::new(),
+            pruned: Vec::new(),
+            uninstantiated: Vec::new(),
+            unknown_categories: Vec::new(),
         };
+        report.entry_points.sort();
 
-        let (dg, entry_points) = self.get_depgraph();
+        let reachable = reachable_from(dg, entry_points);
+        report.pruned = pruned
+            .into_iter()
+            .filter(|(idx, _)| reachable.contains(idx))
+            .map(|(idx, reason)| (dg.node_weight(idx).unwrap().to_string(), reason))
+            .collect();
+        report.pruned.sort();
 
-        let mut auditor = HashMap::new();
+        let mut traversal = Traversal {
+            dg,
+            analyzer: self,
+            options,
+            only_category,
+            entry: NodeIndex::end(),
+            auditor: HashMap::new(),
+            used_auditors: HashSet::new(),
+            nested_auditors: HashMap::new(),
+            outermost_auditors: HashSet::new(),
+            path: Vec::new(),
+            auditor_path: Vec::new(),
+            visited: HashSet::new(),
+            on_path: HashSet::new(),
+            explored: 0,
+            audited: BTreeMap::new(),
+            unaudited: BTreeMap::new(),
+        };
 
-        for entry in entry_points {
+        for &entry in entry_points {
             debug!(
                 "start traversal from entry point {}",
                 dg.node_weight(entry).unwrap()
             );
 
-            let mut visited = HashSet::new();
+            traversal.entry = entry;
+            traversal.visited.clear();
+            traversal.on_path.clear();
+            traversal.on_path.insert(entry);
+            traversal.explored = 0;
+            if self
+                .get_marking(dg.node_weight(entry).unwrap())
+                .map_or(false, |marking| marking.audited.is_some())
+            {
+                traversal.outermost_auditors.insert(entry);
+            }
+
             for edge in dg.edges(entry) {
-                let mut path = vec![edge];
-                traverse(
-                    &dg,
-                    &self.marking_db,
-                    edge,
-                    &mut auditor,
-                    &mut path,
-                    &mut visited,
-                    &mut report,
-                );
+                if traversal.should_follow(edge) {
+                    traversal.path.push(edge);
+                    let fresh = traversal.on_path.insert(edge.target());
+                    traversal.traverse(edge);
+                    if fresh {
+                        traversal.on_path.remove(&edge.target());
+                    }
+                    traversal.path.pop();
+                }
             }
         }
 
-        fn traverse<'a>(
-            dg: &'a DepGraph,
-            marking_db: &PersistentSummaryStore<MarkedItem>,
-            current: EdgeReference<'a, SourceLocation>,
-            auditor: &mut HashMap<String, NodeIndex>,
-            path: &mut Vec<EdgeReference<'a, SourceLocation>>,
-            visited: &mut HashSet<EdgeIndex>,
-            report: &mut AuditReport,
-        ) {
-            let parent = current.source();
-            let dependent = current.target();
-
-            let parent_name = dg.node_weight(parent).unwrap();
-            let dependent_name = dg.node_weight(dependent).unwrap();
-
-            let original_auditor =
-                marking_db
-                    .get(without_type_param(parent_name))
-                    .and_then(|marked_item| {
-                        marked_item.marking.audited.map(|meta| {
-                            (meta.to_string(), auditor.insert(meta.to_string(), parent))
-                        })
-                    });
+        // Each group of paths is reported once, with the shortest path as the
+        // witness. Unaudited groups use the shortest path in the whole graph
+        // since the depth-first search may miss it
+        let unaudited = std::mem::replace(&mut traversal.unaudited, BTreeMap::new());
+        for (key, group) in unaudited {
+            let witness = traversal
+                .shortest_unaudited_path(group.entry, group.sink, &key.category)
+                .filter(|path| path.path.len() <= group.shortest.path.len())
+                .unwrap_or(group.shortest);
+            report.unaudited.push(Finding {
+                key,
+                witness,
+                alternatives: group.count - 1,
+            });
+        }
 
-            let mut skip_children = false;
-
-            if let Some(marked_item) = marking_db.get(without_type_param(dependent_name)) {
-                if let Some(meta) = &marked_item.marking.require_audit {
-                    let dep_path = DepPath::instantiate(&path, dg);
-                    if let Some(&auditor_idx) = auditor.get(meta) {
-                        report
-                            .audited
-                            .push((dg.node_weight(auditor_idx).unwrap().to_string(), dep_path));
-                    } else {
-                        report.unaudited.push(dep_path);
-                        skip_children = true;
-                    }
+        let audited = std::mem::replace(&mut traversal.audited, BTreeMap::new());
+        for ((key, auditor), group) in audited {
+            report.audited.push((
+                auditor,
+                Finding {
+                    key,
+                    witness: group.shortest,
+                    alternatives: group.count - 1,
+                },
+            ));
+        }
+
+        report.unused_auditors = self.unused_auditors(dg, &traversal);
+
+        report
+    }
+
+    // Markings with categories missing from the registry, i.e., the manifest
+    // and the categories declared by all crates. A misspelled category is
+    // never audited. Undeclared subcategories of known categories are only
+    // accepted with hierarchical matching, where auditors of the known
+    // categories cover them. Without a registry, the categories of auditors
+    // are known and only the categories of sensitive functions are checked
+    fn unknown_categories(&self, options: &AuditOptions) -> Vec<UnknownCategory> {
+        let markings = self.markings();
+        let mut known: BTreeSet<String> = self.category_manifest.iter().cloned().collect();
+        self.category_db
+            .for_each(|(_, categories)| known.extend(categories));
+        let never_audited = known.is_empty();
+        if never_audited {
+            known.extend(
+                markings
+                    .values()
+                    .filter_map(|marked_item| marked_item.marking.audited.clone()),
+            );
+        }
+        known.extend(
+            BUILTIN_CATEGORIES
+                .iter()
+                .map(|category| category.to_string()),
+        );
+
+        let mut ret = Vec::new();
+        for (name, marked_item) in markings {
+            let marking = &marked_item.marking;
+            let auditor_category = marking.audited.iter().filter(|_| !never_audited);
+            for category in marking.require_audit.iter().chain(auditor_category) {
+                if !options
+                    .auditing_categories(category)
+                    .iter()
+                    .any(|ancestor| known.contains(*ancestor))
+                {
+                    ret.push(UnknownCategory {
+                        item: (name.clone(), self.remap(marked_item.src_loc.clone())),
+                        category: category.clone(),
+                        closest: closest_category(category, &known).cloned(),
+                        never_audited,
+                    });
                 }
             }
+        }
 
-            if !skip_children {
-                for edge in dg.edges(dependent) {
-                    if !visited.contains(&edge.id()) {
-                        visited.insert(edge.id());
-                        path.push(edge);
-                        traverse(dg, marking_db, edge, auditor, path, visited, report);
-                        path.pop();
-                    }
-                }
+        ret
+    }
+
+    // Auditors are tracked per monomorphized function during the traversal,
+    // but reported per annotated item
+    fn unused_auditors(&self, dg: &DepGraph, traversal: &Traversal) -> Vec<UnusedAuditor> {
+        let origin_of = |idx: &NodeIndex| self.auditor_origin(dg.node_weight(*idx).unwrap());
+        let used: HashSet<String> = traversal
+            .used_auditors
+            .iter()
+            .filter_map(origin_of)
+            .collect();
+        let outermost: HashSet<String> = traversal
+            .outermost_auditors
+            .iter()
+            .filter_map(origin_of)
+            .collect();
+        let mut nested = HashMap::new();
+        for (inner, outer) in &traversal.nested_auditors {
+            if let Some(inner) = origin_of(inner) {
+                nested
+                    .entry(inner)
+                    .or_insert_with(|| dg.node_weight(*outer).unwrap().to_string());
             }
+        }
 
-            if let Some((meta, auditor_node_opt)) = original_auditor {
-                if let Some(auditor_node) = auditor_node_opt {
-                    auditor.insert(meta, auditor_node);
-                } else {
-                    auditor.remove(&meta);
+        let mut ret = Vec::new();
+        for (name, marked_item) in self.markings() {
+            let category = match marked_item.marking.audited {
+                // Auditors of [user_check] parameters audit the entry point
+                // itself rather than the functions it calls. Auditors of
+                // categories not reported in this run are never used
+                Some(category)
+                    if category != USER_CHECK_CATEGORY && traversal.reports_category(&category) =>
+                {
+                    category
+                }
+                _ => continue,
+            };
+
+            let enclosing = if !used.contains(&name) {
+                None
+            } else if outermost.contains(&name) {
+                continue;
+            } else {
+                match nested.remove(&name) {
+                    Some(enclosing) => Some(enclosing),
+                    None => continue,
                 }
+            };
+
+            ret.push(UnusedAuditor {
+                auditor: (name, self.remap(marked_item.src_loc)),
+                category,
+                enclosing,
+            });
+        }
+
+        ret
+    }
+
+    /// The annotated sensitive functions with at least one instance reachable
+    /// from an entry point, whether or not the paths are audited
+    pub fn reachable_sinks(&self) -> HashSet<String> {
+        let (dg, entry_points) = self.get_depgraph();
+        reachable_from(&dg, &entry_points)
+            .into_iter()
+            .filter_map(|idx| self.sink_origin(dg.node_weight(idx).unwrap()))
+            .collect()
+    }
+
+    /// Summarize all callers of each sensitive function. Only functions and
+    /// callers reachable from entry points are included.
+    pub fn sink_callers(&self) -> SinkCallers {
+        let (dg, entry_points) = self.get_depgraph();
+        let reachable = reachable_from(&dg, &entry_points);
+
+        let mut ret = BTreeMap::new();
+        for &node in &reachable {
+            let name = dg.node_weight(node).unwrap();
+            if self
+                .get_marking(name)
+                .map_or(false, |marking| marking.require_audit.is_some())
+            {
+                let mut callers: Vec<ProgPoint> = dg
+                    .edges_directed(node, Direction::Incoming)
+                    .filter(|edge| reachable.contains(&edge.source()))
+                    .map(|edge| {
+                        (
+                            dg.node_weight(edge.source()).unwrap().to_string(),
+                            edge.weight().src_loc.clone(),
+                        )
+                    })
+                    .collect();
+                callers.sort_by(|a, b| {
+                    (&a.0, &a.1.file, a.1.line_no, a.1.col_no).cmp(&(
+                        &b.0,
+                        &b.1.file,
+                        b.1.line_no,
+                        b.1.col_no,
+                    ))
+                });
+                ret.insert(name.to_string(), callers);
             }
         }
 
-        report
+        SinkCallers { callers: ret }
+    }
+
+    pub fn get_depgraph_dot(&self, options: &ExportOptions) -> String {
+        ExportGraph::new(self, options).to_dot(options.edge_labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{finding_sink, matches_entry_instance};
+
+    #[test]
+    fn entry_instance_with_function_name() {
+        assert!(matches_entry_instance(
+            "mycrate::dispatch[0]<u8,>",
+            "dispatch<u8>"
+        ));
+        assert!(matches_entry_instance(
+            "mycrate::dispatch[0]<mycrate::foo[0]::Foo[0]_u8,>",
+            "dispatch<Foo<u8>>"
+        ));
+        assert!(matches_entry_instance(
+            "mycrate::dispatch[0]<u8,alloc::vec[0]::Vec[0]_u32,>",
+            "ecall::dispatch<u8, Vec<u32>>"
+        ));
+        assert!(!matches_entry_instance(
+            "mycrate::handle[0]<u8,>",
+            "dispatch<u8>"
+        ));
+        assert!(!matches_entry_instance(
+            "mycrate::dispatch[0]<u8,u32,>",
+            "dispatch<u8>"
+        ));
+    }
+
+    #[test]
+    fn entry_instance_as_type_argument() {
+        let mono_name = "mycrate::dispatch[0]<mycrate::foo[0]::Foo[0]_u8,>";
+        assert!(matches_entry_instance(mono_name, "Foo<u8>"));
+        assert!(matches_entry_instance(mono_name, "foo::Foo<u8>"));
+        assert!(!matches_entry_instance(mono_name, "Foo<u32>"));
+        assert!(!matches_entry_instance(mono_name, "Bar<u8>"));
+    }
+
+    #[test]
+    fn entry_instance_of_compound_types() {
+        assert!(matches_entry_instance(
+            "mycrate::dispatch[0]<ref_mut_mycrate::Foo[0],>",
+            "&mut Foo"
+        ));
+        assert!(!matches_entry_instance(
+            "mycrate::dispatch[0]<ref_mycrate::Foo[0],>",
+            "&mut Foo"
+        ));
+        assert!(matches_entry_instance(
+            "mycrate::dispatch[0]<tuple_2_u8_my_state[0]::State[0],>",
+            "(u8, State)"
+        ));
+        assert!(matches_entry_instance(
+            "mycrate::dispatch[0]<slice_u8,>",
+            "[u8]"
+        ));
+        assert!(matches_entry_instance(
+            "mycrate::dispatch[0]<array_u8,>",
+            "[u8; 32]"
+        ));
     }
 
-    pub fn get_depgraph_dot(&self) -> String {
-        let dg = self.get_depgraph().0;
-        format!("{:?}", Dot::with_config(&dg, &[Config::EdgeNoLabel]))
+    #[test]
+    fn pseudo_sink_without_site() {
+        assert_eq!(
+            finding_sink("app::run[0]", "@panic::unwrap#8231764937165213"),
+            "@panic::unwrap in app::run[0]"
+        );
+        assert_eq!(
+            finding_sink("app::run[0]", "app::secret[0]::read[0]"),
+            "app::secret[0]::read[0]"
+        );
     }
 }
@@ -1,11 +1,13 @@
+use rustc::hir::def_id::{DefId, LOCAL_CRATE};
 use rustc::hir::*;
 use rustc::ty::fast_reject;
 use rustc::ty::TyCtxt;
-use syntax::ast::{AttrKind, Attribute};
+use rustc_target::spec::abi::Abi;
+use syntax::ast::{AttrKind, Attribute, LitKind};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::summaries::Marking;
+use crate::summaries::{Marking, GLOBAL_STATE_CATEGORY, OCALL_CATEGORY};
 
 struct TaurusAttr {
     string: &'static str,
@@ -29,6 +31,30 @@ impl TaurusAttr {
             AttrKind::DocComment(_) => false,
         })
     }
+
+    /// The list given as `key = "a, b, ..."` to the attribute, if any. No
+    /// other arguments are accepted
+    fn extract_list_arg(&self, attr: &Attribute, key: &str) -> Option<Vec<String>> {
+        let meta_items = attr.meta_item_list()?;
+        let mut ret = None;
+
+        for meta_item in &meta_items {
+            if meta_item.name_or_empty().as_str() == key {
+                let list = meta_item
+                    .value_str()
+                    .unwrap_or_else(|| panic!("#[{}({} = ...)] requires a list", self, key));
+                ret = Some(split_top_level(&list.as_str()));
+            } else {
+                panic!(
+                    "unknown argument {} for #[{}]",
+                    meta_item.name_or_empty(),
+                    self
+                );
+            }
+        }
+
+        ret
+    }
 }
 
 const ATTR_REQUIRE_AUDIT: TaurusAttr = TaurusAttr {
@@ -38,6 +64,13 @@ const ATTR_AUDITED: TaurusAttr = TaurusAttr { string: "audited" };
 const ATTR_ENTRY_POINT: TaurusAttr = TaurusAttr {
     string: "entry_point",
 };
+const ATTR_NO_ALLOC: TaurusAttr = TaurusAttr { string: "no_alloc" };
+const ATTR_CONSTANT_TIME: TaurusAttr = TaurusAttr {
+    string: "constant_time",
+};
+const ATTR_CATEGORIES: TaurusAttr = TaurusAttr {
+    string: "categories",
+};
 
 fn extract_meta_value(attr: &Attribute) -> String {
     attr.value_str()
@@ -50,13 +83,51 @@ fn extract_meta_value(attr: &Attribute) -> String {
         .to_string()
 }
 
+/// Split a comma separated list, ignoring commas nested in brackets. Items are
+/// trimmed, and an empty last item is dropped
+pub(crate) fn split_top_level(list: &str) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    let mut prev = ' ';
+
+    for c in list.chars() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            // The arrow of a function type does not close a bracket
+            '>' if prev == '-' => (),
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                ret.push(current.trim().to_string());
+                current.clear();
+                prev = c;
+                continue;
+            }
+            _ => (),
+        }
+        current.push(c);
+        prev = c;
+    }
+
+    if !current.trim().is_empty() {
+        ret.push(current.trim().to_string());
+    }
+
+    ret
+}
+
 fn marking_from_attributes(attrs: &[Attribute]) -> Marking {
+    let entry_point = ATTR_ENTRY_POINT.match_attributes(attrs);
     Marking {
         require_audit: ATTR_REQUIRE_AUDIT
             .match_attributes(attrs)
             .map(extract_meta_value),
         audited: ATTR_AUDITED.match_attributes(attrs).map(extract_meta_value),
-        is_entry_point: ATTR_ENTRY_POINT.match_attributes(attrs).is_some(),
+        is_entry_point: entry_point.is_some(),
+        entry_instances: entry_point
+            .and_then(|attr| ATTR_ENTRY_POINT.extract_list_arg(attr, "instances")),
+        user_check_params: Vec::new(),
+        no_alloc: ATTR_NO_ALLOC.match_attributes(attrs).is_some(),
     }
 }
 
@@ -69,6 +140,13 @@ fn record_marking(result: &mut HashMap<HirId, Marking>, hir_id: HirId, marking:
             stored_marking.audited = Some(meta);
         }
         stored_marking.is_entry_point = marking.is_entry_point || stored_marking.is_entry_point;
+        if let Some(instances) = marking.entry_instances {
+            stored_marking.entry_instances = Some(instances);
+        }
+        stored_marking
+            .user_check_params
+            .extend(marking.user_check_params);
+        stored_marking.no_alloc = marking.no_alloc || stored_marking.no_alloc;
     } else {
         result.insert(hir_id, marking);
     }
@@ -131,7 +209,12 @@ pub fn extract_annotated_functions(tcx: &TyCtxt<'_>) -> HashMap<HirId, Marking>
                     let ty = tcx.type_of(def_id);
                     // For soundness, ignore generic parameters when simplifying the annotated ADTs
                     if let Some(simplified_self_ty) = fast_reject::simplify_type(*tcx, ty, true) {
-                        if marking.require_audit.is_some() {
+                        if marking.no_alloc {
+                            panic!(
+                                "#[{}] can only annotate functions and methods",
+                                ATTR_NO_ALLOC,
+                            );
+                        } else if marking.require_audit.is_some() {
                             marked_adts.insert(simplified_self_ty, marking);
                         } else {
                             panic!(
@@ -143,16 +226,13 @@ pub fn extract_annotated_functions(tcx: &TyCtxt<'_>) -> HashMap<HirId, Marking>
                         panic!("marked ADT cannot be simplified");
                     }
                 }
-                // Marking a trait does not mean too much. Only the default
-                // implementation (if there is any) of the trait items are
-                // collected. We should consider completely banning marking
-                // traits directly.
+                // Marking a trait marks all of its methods, including those
+                // without a default implementation. The analyzer propagates
+                // these markings to every implementation of the methods
                 ItemKind::Trait(_, _, _, _, trait_items) => {
                     for trait_item in trait_items {
                         if let AssocItemKind::Method { .. } = trait_item.kind {
-                            if trait_item.defaultness.has_value() {
-                                record_marking(&mut funcs, trait_item.id.hir_id, marking.clone());
-                            }
+                            record_marking(&mut funcs, trait_item.id.hir_id, marking.clone());
                         }
                     }
                 }
@@ -163,6 +243,12 @@ pub fn extract_annotated_functions(tcx: &TyCtxt<'_>) -> HashMap<HirId, Marking>
                         }
                     }
                 }
+                ItemKind::Static(..) => {
+                    if marking.require_audit.is_none() || marking.audited.is_some() {
+                        panic!("only #[{}] can annotate statics", ATTR_REQUIRE_AUDIT);
+                    }
+                    record_marking(&mut funcs, item.hir_id, marking);
+                }
                 _ => panic!(
                     "#[{}] and #[{}] can only annotate functions, methods, and ADTs",
                     ATTR_REQUIRE_AUDIT, ATTR_AUDITED,
@@ -171,22 +257,40 @@ pub fn extract_annotated_functions(tcx: &TyCtxt<'_>) -> HashMap<HirId, Marking>
         }
     }
 
+    // Mutable statics are state shared by the whole program. Accessing them
+    // requires audit unless they are annotated with a category of their own
+    for (_, item) in &hir_map.krate().items {
+        if let ItemKind::Static(_, Mutability::Mutable, _) = item.kind {
+            if !funcs
+                .get(&item.hir_id)
+                .map_or(false, |marking| marking.require_audit.is_some())
+            {
+                record_marking(
+                    &mut funcs,
+                    item.hir_id,
+                    Marking::sink(GLOBAL_STATE_CATEGORY),
+                );
+            }
+        }
+    }
+
     // Collect entry points
     for (_, item) in &hir_map.krate().items {
         let marking = marking_from_attributes(&item.attrs);
 
         if marking.is_entry_point {
             if let ItemKind::Fn(_, generics, body_id) = &item.kind {
-                if generics.params.len() == 0 {
-                    record_marking(&mut funcs, hir_map.body_owner(*body_id), marking);
-                    continue;
+                if marking.entry_instances.is_some() && generics.params.len() == 0 {
+                    panic!(
+                        "#[{}(instances = ...)] can only annotate generic functions",
+                        ATTR_ENTRY_POINT,
+                    );
                 }
+                record_marking(&mut funcs, hir_map.body_owner(*body_id), marking);
+                continue;
             }
 
-            panic!(
-                "#[{}] can only annotate functions that are non-generic",
-                ATTR_ENTRY_POINT,
-            );
+            panic!("#[{}] can only annotate functions", ATTR_ENTRY_POINT);
         }
     }
 
@@ -216,3 +320,186 @@ pub fn extract_annotated_functions(tcx: &TyCtxt<'_>) -> HashMap<HirId, Marking>
 
     funcs
 }
+
+/// Collect functions annotated with #[taurus::constant_time] and the indices
+/// of their secret parameters. All parameters are secret unless some are
+/// listed with `#[taurus::constant_time(secret = "...")]`
+pub fn extract_constant_time_functions(tcx: &TyCtxt<'_>) -> Vec<(DefId, Vec<usize>)> {
+    let hir_map = tcx.hir();
+    let mut ret = Vec::new();
+
+    let mut check_attributes = |hir_id: HirId, attrs: &[Attribute]| {
+        if let Some(attr) = ATTR_CONSTANT_TIME.match_attributes(attrs) {
+            let def_id = hir_map.local_def_id(hir_id);
+            let params: Vec<String> = tcx
+                .fn_arg_names(def_id)
+                .iter()
+                .map(|name| name.to_string())
+                .collect();
+            let secret_params = match ATTR_CONSTANT_TIME.extract_list_arg(attr, "secret") {
+                None => (0..params.len()).collect(),
+                Some(names) => names
+                    .iter()
+                    .map(|name| {
+                        params
+                            .iter()
+                            .position(|param| param == name)
+                            .unwrap_or_else(|| {
+                                panic!("unknown parameter {} in #[{}]", name, ATTR_CONSTANT_TIME)
+                            })
+                    })
+                    .collect(),
+            };
+            ret.push((def_id, secret_params));
+        }
+    };
+
+    for (_, item) in &hir_map.krate().items {
+        if let ItemKind::Fn(..) = item.kind {
+            check_attributes(item.hir_id, &item.attrs);
+        }
+    }
+    for (_, item) in &hir_map.krate().impl_items {
+        if let ImplItemKind::Method(..) = item.kind {
+            check_attributes(item.hir_id, &item.attrs);
+        }
+    }
+
+    ret
+}
+
+// The symbol name of a function exported with #[no_mangle] or #[export_name]
+fn exported_symbol(tcx: &TyCtxt<'_>, def_id: DefId) -> Option<String> {
+    let attrs = tcx.codegen_fn_attrs(def_id);
+    if let Some(name) = attrs.export_name {
+        Some(name.to_string())
+    } else if attrs.flags.contains(CodegenFnAttrFlags::NO_MANGLE) {
+        Some(tcx.item_name(def_id).to_string())
+    } else {
+        None
+    }
+}
+
+/// Mark functions following the entry point conventions of TEE programs as
+/// entry points, in addition to those explicitly annotated. ECALLs, given as a
+/// map from their names to their [user_check] parameters, are always marked.
+/// If `by_convention` is set, `#[no_mangle] pub extern "C"` functions and the
+/// `main` function of the crate are marked too.
+pub fn discover_entry_points(
+    tcx: &TyCtxt<'_>,
+    funcs: &mut HashMap<HirId, Marking>,
+    ecalls: &HashMap<String, Vec<String>>,
+    by_convention: bool,
+) {
+    let hir_map = tcx.hir();
+    let main_fn = tcx.entry_fn(LOCAL_CRATE).map(|(def_id, _)| def_id);
+
+    for (_, item) in &hir_map.krate().items {
+        if let ItemKind::Fn(sig, generics, body_id) = &item.kind {
+            let def_id = hir_map.local_def_id(item.hir_id);
+            let symbol = exported_symbol(tcx, def_id);
+
+            let ecall = ecalls
+                .get(&*item.ident.as_str())
+                .or_else(|| symbol.as_ref().and_then(|sym| ecalls.get(sym)));
+            let is_conventional = by_convention
+                && (main_fn == Some(def_id)
+                    || (symbol.is_some() && sig.header.abi == Abi::C && item.vis.node.is_pub()));
+
+            if ecall.is_none() && !is_conventional {
+                continue;
+            }
+
+            // Only the lifetimes of an entry point may be generic since the
+            // instances to audit are not known
+            let is_generic = generics.params.iter().any(|param| match param.kind {
+                GenericParamKind::Lifetime { .. } => false,
+                _ => true,
+            });
+            if is_generic {
+                tcx.sess.span_warn(
+                    item.span,
+                    &format!(
+                        "generic function `{}` is not discovered as an entry point, \
+                         annotate it with #[{}(instances = ...)]",
+                        item.ident, ATTR_ENTRY_POINT
+                    ),
+                );
+            } else {
+                debug!("discovered entry point {}", item.ident);
+                record_marking(
+                    funcs,
+                    hir_map.body_owner(*body_id),
+                    Marking {
+                        is_entry_point: true,
+                        user_check_params: ecall.cloned().unwrap_or_default(),
+                        ..Marking::default()
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Mark foreign functions declared as OCALLs as requiring audit since calling
+/// them passes data out of the enclave
+pub fn mark_ocalls(
+    tcx: &TyCtxt<'_>,
+    funcs: &mut HashMap<HirId, Marking>,
+    ocalls: &HashSet<String>,
+) {
+    for (_, item) in &tcx.hir().krate().items {
+        if let ItemKind::ForeignMod(foreign_mod) = &item.kind {
+            for foreign_item in foreign_mod.items.iter() {
+                if let ForeignItemKind::Fn(..) = foreign_item.kind {
+                    if ocalls.contains(&*foreign_item.ident.as_str()) {
+                        record_marking(funcs, foreign_item.hir_id, Marking::sink(OCALL_CATEGORY));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Warn about functions exported from the crate that are not entry points.
+/// They can be called from outside but are never audited.
+pub fn report_uncovered_exports(tcx: &TyCtxt<'_>, funcs: &HashMap<HirId, Marking>) {
+    let hir_map = tcx.hir();
+
+    for (_, item) in &hir_map.krate().items {
+        if let ItemKind::Fn(..) = &item.kind {
+            let def_id = hir_map.local_def_id(item.hir_id);
+            if let Some(symbol) = exported_symbol(tcx, def_id) {
+                if !funcs
+                    .get(&item.hir_id)
+                    .map_or(false, |marking| marking.is_entry_point)
+                {
+                    tcx.sess.span_warn(
+                        item.span,
+                        &format!(
+                            "exported symbol `{}` is not covered by an entry point",
+                            symbol
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Categories declared with `#![taurus::categories("crypto", ...)]` on the crate
+pub fn extract_categories(tcx: &TyCtxt<'_>) -> Vec<String> {
+    let attr = match ATTR_CATEGORIES.match_attributes(&tcx.hir().krate().attrs) {
+        Some(attr) => attr,
+        None => return Vec::new(),
+    };
+
+    attr.meta_item_list()
+        .unwrap_or_else(|| panic!("#![{}(...)] requires a list of categories", ATTR_CATEGORIES))
+        .iter()
+        .map(|nested| match nested.literal().map(|lit| &lit.kind) {
+            Some(LitKind::Str(category, _)) => category.to_string(),
+            _ => panic!("#![{}(...)] only accepts string literals", ATTR_CATEGORIES),
+        })
+        .collect()
+}
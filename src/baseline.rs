@@ -0,0 +1,111 @@
+// A baseline records the findings of an audit such that later audits only
+// report the differences. Findings are identified by their entry points, sinks
+// and categories, which are stable across unrelated changes of the code.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::path::Path;
+
+use crate::analyzer::{AuditReport, FindingKey};
+use crate::summaries::USER_CHECK_CATEGORY;
+
+const BASELINE_HEADER: &str = "# taurus baseline: entry point, sink, category";
+
+pub struct Baseline {
+    findings: BTreeSet<FindingKey>,
+}
+
+// Entry points with unaudited [user_check] parameters are findings whose
+// sinks are the entry points themselves
+fn user_check_key(entry_point: &str) -> FindingKey {
+    FindingKey {
+        entry_point: entry_point.to_string(),
+        sink: entry_point.to_string(),
+        category: USER_CHECK_CATEGORY.to_string(),
+    }
+}
+
+impl Baseline {
+    pub fn from_report(report: &AuditReport) -> Self {
+        let mut findings: BTreeSet<FindingKey> = report
+            .unaudited
+            .iter()
+            .map(|finding| finding.key.clone())
+            .collect();
+        findings.extend(
+            report
+                .user_check
+                .iter()
+                .map(|(entry, _)| user_check_key(&entry.0)),
+        );
+
+        Self { findings }
+    }
+
+    pub fn len(&self) -> usize {
+        self.findings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let mut findings = BTreeSet::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 3 {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("malformed baseline entry: {}", line),
+                ));
+            }
+
+            findings.insert(FindingKey {
+                entry_point: fields[0].to_string(),
+                sink: fields[1].to_string(),
+                category: fields[2].to_string(),
+            });
+        }
+
+        Ok(Self { findings })
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+
+        writeln!(file, "{}", BASELINE_HEADER)?;
+        for key in &self.findings {
+            writeln!(file, "{}\t{}\t{}", key.entry_point, key.sink, key.category)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the findings recorded in the baseline from `report`, such that
+    /// only new findings remain. Findings in the baseline that no longer show
+    /// up are recorded in the report as resolved.
+    pub fn apply(&self, report: &mut AuditReport) {
+        let current = Self::from_report(report);
+
+        report
+            .unaudited
+            .retain(|finding| !self.findings.contains(&finding.key));
+        report
+            .user_check
+            .retain(|(entry, _)| !self.findings.contains(&user_check_key(&entry.0)));
+
+        report.resolved = self
+            .findings
+            .difference(&current.findings)
+            .cloned()
+            .collect();
+    }
+}
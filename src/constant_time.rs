@@ -0,0 +1,225 @@
+// Check that #[taurus::constant_time] functions do not branch on, index with,
+// or divide by secret data, any of which makes the execution time depend on
+// the secret. Secret parameters are tracked through the locals of the MIR and
+// into callees in the local crate, which are checked with the parameters
+// receiving secret data. Callees in other crates are not checked.
+
+use rustc::hir::def_id::DefId;
+use rustc::mir::visit::{PlaceContext, Visitor};
+use rustc::mir::{
+    BinOp, Body, Local, Location, Operand, Place, PlaceBase, ProjectionElem, Rvalue, StatementKind,
+    Terminator, TerminatorKind,
+};
+use rustc::ty::{Instance, TyCtxt, TyKind};
+use syntax_pos::Span;
+
+use std::collections::{BTreeSet, HashSet};
+
+// Locals read by a part of the MIR
+struct Reads {
+    locals: Vec<Local>,
+}
+
+impl<'tcx> Visitor<'tcx> for Reads {
+    fn visit_local(&mut self, local: &Local, _context: PlaceContext, _location: Location) {
+        self.locals.push(*local);
+    }
+}
+
+fn reads_secret<'tcx>(
+    operand: &Operand<'tcx>,
+    location: Location,
+    tainted: &HashSet<Local>,
+) -> bool {
+    let mut reads = Reads { locals: Vec::new() };
+    reads.visit_operand(operand, location);
+    reads.locals.iter().any(|local| tainted.contains(local))
+}
+
+fn written_local(place: &Place<'_>) -> Option<Local> {
+    match place.base {
+        PlaceBase::Local(local) => Some(local),
+        PlaceBase::Static(..) => None,
+    }
+}
+
+// Locals that may hold data derived from the secret parameters. Data flows
+// through assignments and from the arguments of calls to their results
+fn taint<'tcx>(body: &Body<'tcx>, secret_params: &BTreeSet<usize>) -> HashSet<Local> {
+    let mut tainted: HashSet<Local> = body
+        .args_iter()
+        .enumerate()
+        .filter(|(idx, _)| secret_params.contains(idx))
+        .map(|(_, local)| local)
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (block, data) in body.basic_blocks().iter_enumerated() {
+            for (statement_index, statement) in data.statements.iter().enumerate() {
+                let location = Location {
+                    block,
+                    statement_index,
+                };
+                if let StatementKind::Assign(assign) = &statement.kind {
+                    let (place, rvalue) = &**assign;
+                    let mut reads = Reads { locals: Vec::new() };
+                    reads.visit_rvalue(rvalue, location);
+                    if reads.locals.iter().any(|local| tainted.contains(local)) {
+                        if let Some(local) = written_local(place) {
+                            changed |= tainted.insert(local);
+                        }
+                    }
+                }
+            }
+
+            let location = body.terminator_loc(block);
+            if let TerminatorKind::Call {
+                args,
+                destination: Some((place, _)),
+                ..
+            } = &data.terminator().kind
+            {
+                if args.iter().any(|arg| reads_secret(arg, location, &tainted)) {
+                    if let Some(local) = written_local(place) {
+                        changed |= tainted.insert(local);
+                    }
+                }
+            }
+        }
+    }
+
+    tainted
+}
+
+// Operations whose execution time depends on secret data
+struct Violations<'a> {
+    tainted: &'a HashSet<Local>,
+    found: Vec<(Location, &'static str)>,
+}
+
+impl<'a, 'tcx> Visitor<'tcx> for Violations<'a> {
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, location: Location) {
+        for elem in place.projection.iter() {
+            if let ProjectionElem::Index(local) = elem {
+                if self.tainted.contains(local) {
+                    self.found.push((location, "indexing with secret data"));
+                }
+            }
+        }
+
+        self.super_place(place, context, location);
+    }
+
+    fn visit_rvalue(&mut self, rvalue: &Rvalue<'tcx>, location: Location) {
+        match rvalue {
+            Rvalue::BinaryOp(op, lhs, rhs) | Rvalue::CheckedBinaryOp(op, lhs, rhs)
+                if *op == BinOp::Div || *op == BinOp::Rem =>
+            {
+                if reads_secret(lhs, location, self.tainted)
+                    || reads_secret(rhs, location, self.tainted)
+                {
+                    self.found.push((location, "division of secret data"));
+                }
+            }
+            _ => (),
+        }
+
+        self.super_rvalue(rvalue, location);
+    }
+
+    fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, location: Location) {
+        if let TerminatorKind::SwitchInt { discr, .. } = &terminator.kind {
+            if reads_secret(discr, location, self.tainted) {
+                self.found.push((location, "branching on secret data"));
+            }
+        }
+
+        self.super_terminator(terminator, location);
+    }
+}
+
+pub struct ConstantTimeChecker<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    // Functions already checked, with their secret parameters
+    checked: HashSet<(DefId, BTreeSet<usize>)>,
+    reported: HashSet<Span>,
+}
+
+impl<'tcx> ConstantTimeChecker<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>) -> Self {
+        Self {
+            tcx,
+            checked: HashSet::new(),
+            reported: HashSet::new(),
+        }
+    }
+
+    /// Check a #[taurus::constant_time] function and its callees, warning
+    /// about each violation
+    pub fn check(&mut self, root: DefId, secret_params: &[usize]) {
+        let mut worklist: Vec<(DefId, BTreeSet<usize>)> =
+            vec![(root, secret_params.iter().cloned().collect())];
+
+        while let Some((def_id, secret_params)) = worklist.pop() {
+            if !def_id.is_local()
+                || !self.tcx.is_mir_available(def_id)
+                || !self.checked.insert((def_id, secret_params.clone()))
+            {
+                continue;
+            }
+
+            let body = self.tcx.optimized_mir(def_id);
+            let tainted = taint(body, &secret_params);
+
+            let mut violations = Violations {
+                tainted: &tainted,
+                found: Vec::new(),
+            };
+            violations.visit_body(body);
+            for (location, violation) in violations.found {
+                self.report(root, def_id, body.source_info(location).span, violation);
+            }
+
+            for (block, data) in body.basic_blocks().iter_enumerated() {
+                let location = body.terminator_loc(block);
+                if let TerminatorKind::Call { func, args, .. } = &data.terminator().kind {
+                    if let TyKind::FnDef(callee, substs) = func.ty(body, self.tcx).kind {
+                        let secret_args: BTreeSet<usize> = args
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, arg)| reads_secret(arg, location, &tainted))
+                            .map(|(idx, _)| idx)
+                            .collect();
+                        if !secret_args.is_empty() {
+                            let param_env = self.tcx.param_env(def_id);
+                            let callee = Instance::resolve(self.tcx, param_env, callee, substs)
+                                .map_or(callee, |instance| instance.def.def_id());
+                            worklist.push((callee, secret_args));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn report(&mut self, root: DefId, def_id: DefId, span: Span, violation: &str) {
+        if !self.reported.insert(span) {
+            return;
+        }
+
+        let mut diag = self
+            .tcx
+            .sess
+            .struct_span_warn(span, &format!("{} in constant-time code", violation));
+        if def_id != root {
+            diag.span_note(
+                self.tcx.def_span(root),
+                "reached from this #[taurus::constant_time] function",
+            );
+        }
+        diag.emit();
+    }
+}
@@ -0,0 +1,153 @@
+// Coverage metrics tracking the progress of an audit: how many sensitive
+// functions exist, how many are reachable from entry points, and how many of
+// the reachable ones are audited, per category and per crate.
+
+use std::collections::BTreeMap;
+
+use crate::analyzer::{category_ancestors, sink_function, AuditReport, TaurusAnalyzer};
+use crate::query::crate_name;
+
+#[derive(Serialize, Default, Clone)]
+pub struct CoverageStats {
+    // Functions marked #[taurus::require_audit]
+    pub sinks: usize,
+    // Sensitive functions reachable from at least one entry point
+    pub reachable_sinks: usize,
+    // Pairs of entry points and sensitive functions, with or without auditors
+    pub audited_paths: usize,
+    pub unaudited_paths: usize,
+}
+
+impl CoverageStats {
+    /// Fraction of the paths to sensitive functions that are audited
+    pub fn audited_ratio(&self) -> f64 {
+        let paths = self.audited_paths + self.unaudited_paths;
+        if paths == 0 {
+            1.0
+        } else {
+            self.audited_paths as f64 / paths as f64
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Coverage {
+    pub total: CoverageStats,
+    // Subcategories are also counted in their parent categories
+    pub by_category: BTreeMap<String, CoverageStats>,
+    pub by_crate: BTreeMap<String, CoverageStats>,
+    // Possibly dead code, or code whose entry points are not annotated
+    pub unreachable_sinks: Vec<String>,
+}
+
+impl Coverage {
+    pub fn compute(analyzer: &TaurusAnalyzer, report: &AuditReport) -> Self {
+        let mut ret = Coverage {
+            total: CoverageStats::default(),
+            by_category: BTreeMap::new(),
+            by_crate: BTreeMap::new(),
+            unreachable_sinks: Vec::new(),
+        };
+
+        // Findings only cover reported categories and stop at unaudited
+        // sinks, so reachability is taken from the graph itself
+        let reachable = analyzer.reachable_sinks();
+        let paths = report
+            .unaudited
+            .iter()
+            .map(|finding| (finding, false))
+            .chain(report.audited.iter().map(|(_, finding)| (finding, true)));
+
+        for (finding, is_audited) in paths {
+            // Built-in sinks have no marking and count towards their own crate
+            // or, for pseudo functions, the crate containing their sites
+            let origin = analyzer
+                .sink_origin(&finding.key.sink)
+                .unwrap_or_else(|| sink_function(&finding.key.sink).to_string());
+            ret.count(&origin, &finding.key.category, |stats| {
+                if is_audited {
+                    stats.audited_paths += 1;
+                } else {
+                    stats.unaudited_paths += 1;
+                }
+            });
+        }
+
+        for (name, marked_item) in analyzer.markings() {
+            if let Some(category) = marked_item.marking.require_audit {
+                let is_reachable = reachable.contains(&name);
+                ret.count(&name, &category, |stats| {
+                    stats.sinks += 1;
+                    if is_reachable {
+                        stats.reachable_sinks += 1;
+                    }
+                });
+                if !is_reachable {
+                    ret.unreachable_sinks.push(name);
+                }
+            }
+        }
+
+        ret
+    }
+
+    // Update the total and the stats of the crate of `name` and of all
+    // categories containing `category`
+    fn count(&mut self, name: &str, category: &str, update: impl Fn(&mut CoverageStats)) {
+        update(&mut self.total);
+        update(self.by_crate.entry(crate_name(name)).or_default());
+        for category in category_ancestors(category) {
+            update(self.by_category.entry(category.to_string()).or_default());
+        }
+    }
+}
+
+fn write_table(
+    f: &mut std::fmt::Formatter,
+    title: &str,
+    rows: &BTreeMap<String, CoverageStats>,
+    total: &CoverageStats,
+) -> std::fmt::Result {
+    write!(
+        f,
+        "{:<40} {:>8} {:>10} {:>8} {:>10} {:>9}\n",
+        title, "Sinks", "Reachable", "Audited", "Unaudited", "Coverage"
+    )?;
+    for (name, stats) in rows
+        .iter()
+        .chain(std::iter::once((&"total".to_string(), total)))
+    {
+        write!(
+            f,
+            "{:<40} {:>8} {:>10} {:>8} {:>10} {:>8.1}%\n",
+            name,
+            stats.sinks,
+            stats.reachable_sinks,
+            stats.audited_paths,
+            stats.unaudited_paths,
+            stats.audited_ratio() * 100.0
+        )?;
+    }
+
+    Ok(())
+}
+
+impl std::fmt::Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write_table(f, "Category", &self.by_category, &self.total)?;
+        write!(f, "\n")?;
+        write_table(f, "Crate", &self.by_crate, &self.total)?;
+
+        if !self.unreachable_sinks.is_empty() {
+            write!(
+                f,
+                "\nSensitive functions not reachable from any entry point:\n"
+            )?;
+            for name in &self.unreachable_sinks {
+                write!(f, "   {}\n", name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
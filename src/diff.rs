@@ -0,0 +1,161 @@
+// Compare the depstores of two builds, e.g., before and after a change, to show
+// how the trust surface of the program changed.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::analyzer::{AuditOptions, AuditReport, FindingKey, TaurusAnalyzer};
+use crate::summaries::{MarkedItem, Marking};
+
+pub struct StoreDiff {
+    pub new_entry_points: Vec<String>,
+    pub removed_entry_points: Vec<String>,
+    pub new_sinks: Vec<String>,
+    pub removed_sinks: Vec<String>,
+    // Entry points reaching sinks in the new build but not in the old one
+    pub new_reachable: Vec<FindingKey>,
+    pub removed_reachable: Vec<FindingKey>,
+    // Unaudited in the old build, audited in the new build
+    pub newly_audited: Vec<FindingKey>,
+    // Audited in the old build, unaudited in the new build
+    pub newly_unaudited: Vec<FindingKey>,
+    pub changed_markings: Vec<(String, Marking, Marking)>,
+}
+
+// Findings of a report, mapped to whether they are audited
+fn findings(report: &AuditReport) -> BTreeMap<FindingKey, bool> {
+    let mut ret = BTreeMap::new();
+    for finding in &report.unaudited {
+        ret.insert(finding.key.clone(), false);
+    }
+    for (_, finding) in &report.audited {
+        // A sink may be reached both with and without an auditor
+        ret.entry(finding.key.clone()).or_insert(true);
+    }
+    ret
+}
+
+fn entry_points(analyzer: &TaurusAnalyzer) -> BTreeSet<String> {
+    let (dg, entry_points) = analyzer.get_depgraph();
+    entry_points
+        .into_iter()
+        .map(|idx| dg.node_weight(idx).unwrap().to_string())
+        .collect()
+}
+
+fn sinks(markings: &BTreeMap<String, MarkedItem>) -> BTreeSet<String> {
+    markings
+        .iter()
+        .filter(|(_, marked_item)| marked_item.marking.require_audit.is_some())
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+fn added<T: Ord + Clone>(old: &BTreeSet<T>, new: &BTreeSet<T>) -> Vec<T> {
+    new.difference(old).cloned().collect()
+}
+
+impl StoreDiff {
+    pub fn compute(old: &TaurusAnalyzer, new: &TaurusAnalyzer) -> Self {
+        let old_markings = old.markings();
+        let new_markings = new.markings();
+
+        let old_entry_points = entry_points(old);
+        let new_entry_points = entry_points(new);
+        let old_sinks = sinks(&old_markings);
+        let new_sinks = sinks(&new_markings);
+
+        let options = AuditOptions::default();
+        let old_findings = findings(&old.audit(&options));
+        let new_findings = findings(&new.audit(&options));
+        let old_keys: BTreeSet<FindingKey> = old_findings.keys().cloned().collect();
+        let new_keys: BTreeSet<FindingKey> = new_findings.keys().cloned().collect();
+
+        let mut newly_audited = Vec::new();
+        let mut newly_unaudited = Vec::new();
+        for (key, &is_audited) in &new_findings {
+            match old_findings.get(key) {
+                Some(false) if is_audited => newly_audited.push(key.clone()),
+                Some(true) if !is_audited => newly_unaudited.push(key.clone()),
+                _ => (),
+            }
+        }
+
+        let changed_markings = new_markings
+            .iter()
+            .filter_map(|(name, new_item)| {
+                old_markings.get(name).and_then(|old_item| {
+                    if old_item.marking != new_item.marking {
+                        Some((
+                            name.to_string(),
+                            old_item.marking.clone(),
+                            new_item.marking.clone(),
+                        ))
+                    } else {
+                        None
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            new_entry_points: added(&old_entry_points, &new_entry_points),
+            removed_entry_points: added(&new_entry_points, &old_entry_points),
+            new_sinks: added(&old_sinks, &new_sinks),
+            removed_sinks: added(&new_sinks, &old_sinks),
+            new_reachable: added(&old_keys, &new_keys),
+            removed_reachable: added(&new_keys, &old_keys),
+            newly_audited,
+            newly_unaudited,
+            changed_markings,
+        }
+    }
+}
+
+fn write_section<T: std::fmt::Display>(
+    f: &mut std::fmt::Formatter,
+    title: &str,
+    items: &[T],
+) -> std::fmt::Result {
+    if !items.is_empty() {
+        write!(f, "{} ({}):\n", title, items.len())?;
+        for item in items {
+            write!(f, "   {}\n", item)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl std::fmt::Display for StoreDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write_section(f, "New entry points", &self.new_entry_points)?;
+        write_section(f, "Removed entry points", &self.removed_entry_points)?;
+        write_section(f, "New sensitive functions", &self.new_sinks)?;
+        write_section(f, "Removed sensitive functions", &self.removed_sinks)?;
+        write_section(
+            f,
+            "Newly reachable sensitive functions",
+            &self.new_reachable,
+        )?;
+        write_section(
+            f,
+            "No longer reachable sensitive functions",
+            &self.removed_reachable,
+        )?;
+        write_section(f, "Newly audited", &self.newly_audited)?;
+        write_section(f, "Newly unaudited", &self.newly_unaudited)?;
+
+        if !self.changed_markings.is_empty() {
+            write!(
+                f,
+                "Changed annotations ({}):\n",
+                self.changed_markings.len()
+            )?;
+            for (name, old, new) in &self.changed_markings {
+                write!(f, "   {}: {} -> {}\n", name, old, new)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,502 @@
+// A parser for the Enclave Definition Language (EDL) used by SGX projects to
+// describe the interface between the trusted and untrusted parts of a program.
+// Only the parts of the language relevant to auditing are interpreted.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Punct(char),
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut ret = Vec::new();
+    let mut chars = src.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => prev = c,
+                        None => return Err("unterminated comment".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => string.push(c),
+                        None => return Err("unterminated string literal".to_string()),
+                    }
+                }
+                ret.push(Token::Str(string));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                ret.push(Token::Ident(ident));
+            }
+            c => ret.push(Token::Punct(c)),
+        }
+    }
+
+    Ok(ret)
+}
+
+#[derive(Clone, Debug)]
+pub struct EdlParam {
+    pub name: String,
+    pub ty: String,
+    // Pointer attributes such as `in`, `out`, `user_check` and `size=len`
+    pub attributes: Vec<String>,
+}
+
+impl EdlParam {
+    /// Pointers marked `[user_check]` are not checked or copied by the edger8r
+    /// generated bridge and must be validated by the function itself
+    pub fn is_user_check(&self) -> bool {
+        self.attributes.iter().any(|attr| attr == "user_check")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EdlFunction {
+    pub name: String,
+    pub is_public: bool,
+    pub params: Vec<EdlParam>,
+}
+
+impl EdlFunction {
+    pub fn user_check_params(&self) -> Vec<String> {
+        self.params
+            .iter()
+            .filter(|param| param.is_user_check())
+            .map(|param| param.name.clone())
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct EdlImport {
+    file: String,
+    // `None` means everything is imported
+    functions: Option<Vec<String>>,
+}
+
+#[derive(Default, Debug)]
+pub struct Edl {
+    // ECALLs, i.e., functions implemented in the enclave
+    pub trusted: Vec<EdlFunction>,
+    // OCALLs, i.e., functions implemented outside the enclave
+    pub untrusted: Vec<EdlFunction>,
+    imports: Vec<EdlImport>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let ret = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        ret
+    }
+
+    fn expect_punct(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Punct(c)) if c == expected => Ok(()),
+            other => Err(format!("expecting '{}', found {:?}", expected, other)),
+        }
+    }
+
+    // Parse a single parameter from its tokens, e.g., `[in, size=len] const uint8_t* buf`
+    fn parse_param(tokens: &[Token]) -> Result<EdlParam, String> {
+        let mut attributes = Vec::new();
+        let mut rest = tokens;
+
+        if tokens.first() == Some(&Token::Punct('[')) {
+            let end = tokens
+                .iter()
+                .position(|token| token == &Token::Punct(']'))
+                .ok_or_else(|| "unterminated parameter attributes".to_string())?;
+            let mut attr = String::new();
+            for token in &tokens[1..end] {
+                match token {
+                    Token::Punct(',') => {
+                        attributes.push(std::mem::replace(&mut attr, String::new()))
+                    }
+                    Token::Ident(ident) | Token::Str(ident) => attr.push_str(ident),
+                    Token::Punct(c) => attr.push(*c),
+                }
+            }
+            if !attr.is_empty() {
+                attributes.push(attr);
+            }
+            rest = &tokens[end + 1..];
+        }
+
+        // The parameter name is the last identifier outside of array brackets
+        let array_start = rest
+            .iter()
+            .position(|token| token == &Token::Punct('['))
+            .unwrap_or_else(|| rest.len());
+        let name_pos = rest[..array_start]
+            .iter()
+            .rposition(|token| match token {
+                Token::Ident(_) => true,
+                _ => false,
+            })
+            .ok_or_else(|| "missing parameter name".to_string())?;
+
+        let name = match &rest[name_pos] {
+            Token::Ident(ident) => ident.clone(),
+            _ => unreachable!(),
+        };
+        let ty = rest[..name_pos]
+            .iter()
+            .map(|token| match token {
+                Token::Ident(ident) | Token::Str(ident) => ident.clone(),
+                Token::Punct(c) => c.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Ok(EdlParam {
+            name,
+            ty,
+            attributes,
+        })
+    }
+
+    // Parse a function declaration up to the terminating semicolon
+    fn parse_function(&mut self) -> Result<EdlFunction, String> {
+        let is_public = self.peek() == Some(&Token::Ident("public".to_string()));
+        let mut name = None;
+        let mut params = Vec::new();
+
+        loop {
+            match self.next() {
+                Some(Token::Punct(';')) => break,
+                Some(Token::Punct('(')) if name.is_none() => {
+                    match self.tokens.get(self.pos - 2) {
+                        Some(Token::Ident(ident)) => name = Some(ident.clone()),
+                        _ => return Err("missing function name".to_string()),
+                    }
+
+                    let mut depth = 1;
+                    let mut param = Vec::new();
+                    while depth > 0 {
+                        let token = self
+                            .next()
+                            .ok_or_else(|| "unterminated parameter list".to_string())?;
+                        // Brackets are tracked to keep attribute lists intact
+                        match token {
+                            Token::Punct('(') | Token::Punct('[') => depth += 1,
+                            Token::Punct(')') | Token::Punct(']') => depth -= 1,
+                            _ => (),
+                        }
+                        if depth == 0 || (depth == 1 && token == Token::Punct(',')) {
+                            // A sole `void` means an empty parameter list
+                            if !param.is_empty() && param != [Token::Ident("void".to_string())] {
+                                params.push(Self::parse_param(&param)?);
+                            }
+                            param.clear();
+                        } else {
+                            param.push(token);
+                        }
+                    }
+                }
+                // Trailing `allow(...)` lists and the like
+                Some(_) => (),
+                None => return Err("unterminated function declaration".to_string()),
+            }
+        }
+
+        Ok(EdlFunction {
+            name: name.ok_or_else(|| "missing parameter list".to_string())?,
+            is_public,
+            params,
+        })
+    }
+
+    // Parse `from "file.edl" import *;` or `from "file.edl" import a, b;`
+    fn parse_import(&mut self) -> Result<EdlImport, String> {
+        let file = match self.next() {
+            Some(Token::Str(file)) => file,
+            other => return Err(format!("expecting file name, found {:?}", other)),
+        };
+        match self.next() {
+            Some(Token::Ident(ref ident)) if ident == "import" => (),
+            other => return Err(format!("expecting 'import', found {:?}", other)),
+        }
+
+        let mut functions = Some(Vec::new());
+        loop {
+            match self.next() {
+                Some(Token::Punct(';')) => break,
+                Some(Token::Punct('*')) => functions = None,
+                Some(Token::Ident(ident)) => {
+                    if let Some(functions) = &mut functions {
+                        functions.push(ident);
+                    }
+                }
+                Some(Token::Punct(',')) => (),
+                other => return Err(format!("unexpected {:?} in import", other)),
+            }
+        }
+
+        Ok(EdlImport { file, functions })
+    }
+
+    // Skip a (possibly nested) block whose opening brace has been consumed
+    fn skip_block(&mut self) -> Result<(), String> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next() {
+                Some(Token::Punct('{')) => depth += 1,
+                Some(Token::Punct('}')) => depth -= 1,
+                Some(_) => (),
+                None => return Err("unterminated block".to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_functions(&mut self) -> Result<Vec<EdlFunction>, String> {
+        let mut ret = Vec::new();
+
+        self.expect_punct('{')?;
+        while self.peek() != Some(&Token::Punct('}')) {
+            if self.peek().is_none() {
+                return Err("unterminated block".to_string());
+            }
+            ret.push(self.parse_function()?);
+        }
+        self.expect_punct('}')?;
+        if self.peek() == Some(&Token::Punct(';')) {
+            self.next();
+        }
+
+        Ok(ret)
+    }
+
+    fn parse(&mut self) -> Result<Edl, String> {
+        let mut ret = Edl::default();
+
+        match self.next() {
+            Some(Token::Ident(ref ident)) if ident == "enclave" => (),
+            other => return Err(format!("expecting 'enclave', found {:?}", other)),
+        }
+        self.expect_punct('{')?;
+
+        loop {
+            match self.next() {
+                Some(Token::Ident(ref ident)) if ident == "trusted" => {
+                    ret.trusted.extend(self.parse_functions()?)
+                }
+                Some(Token::Ident(ref ident)) if ident == "untrusted" => {
+                    ret.untrusted.extend(self.parse_functions()?)
+                }
+                Some(Token::Ident(ref ident)) if ident == "from" => {
+                    ret.imports.push(self.parse_import()?)
+                }
+                Some(Token::Punct('}')) => break,
+                // Imports, includes, and type definitions are skipped
+                Some(Token::Punct('{')) => self.skip_block()?,
+                Some(_) => (),
+                None => return Err("unterminated enclave definition".to_string()),
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+impl Edl {
+    pub fn parse(src: &str) -> Result<Self, String> {
+        Parser {
+            tokens: tokenize(src)?,
+            pos: 0,
+        }
+        .parse()
+    }
+
+    /// Parse an EDL file together with the files it imports. Imported files are
+    /// looked up relative to the importing file and skipped if not found, since
+    /// they usually belong to the SGX SDK.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        Self::from_file_importing(path, &mut HashSet::new())
+    }
+
+    // `importing` holds the canonical paths of the files being imported, such
+    // that cyclic imports are skipped
+    fn from_file_importing(path: &Path, importing: &mut HashSet<PathBuf>) -> Result<Self, String> {
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        if !importing.insert(canonical.clone()) {
+            return Ok(Self::default());
+        }
+
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let mut ret = Self::parse(&src).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        for import in std::mem::replace(&mut ret.imports, Vec::new()) {
+            let import_path = path.parent().unwrap_or(Path::new("")).join(&import.file);
+            if !import_path.exists() {
+                warn!("skipping EDL import {}", import_path.display());
+                continue;
+            }
+
+            let imported = Self::from_file_importing(&import_path, importing)?;
+            let is_imported = |func: &EdlFunction| {
+                import
+                    .functions
+                    .as_ref()
+                    .map_or(true, |functions| functions.contains(&func.name))
+            };
+            ret.trusted.extend(
+                imported
+                    .trusted
+                    .into_iter()
+                    .filter(|func| is_imported(func)),
+            );
+            ret.untrusted.extend(
+                imported
+                    .untrusted
+                    .into_iter()
+                    .filter(|func| is_imported(func)),
+            );
+        }
+
+        importing.remove(&canonical);
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Edl;
+
+    const ENCLAVE: &str = r#"
+        enclave {
+            from "sgx_tstd.edl" import *;
+            include "sgx_urts.h"
+
+            /* Types are not interpreted */
+            struct point_t { int x; int y; };
+
+            trusted {
+                public sgx_status_t ecall_run([user_check] uint8_t* ptr,
+                                              [in, size=len] const uint8_t* buf,
+                                              size_t len);
+                // No parameters
+                public void ecall_init(void);
+                void ecall_private(int values[4]);
+            };
+
+            untrusted {
+                void ocall_print([in, string] const char* msg) allow(ecall_init, ecall_run);
+            };
+        };
+    "#;
+
+    #[test]
+    fn parse_functions() {
+        let edl = Edl::parse(ENCLAVE).unwrap();
+        let trusted: Vec<_> = edl.trusted.iter().map(|func| func.name.as_str()).collect();
+        assert_eq!(trusted, ["ecall_run", "ecall_init", "ecall_private"]);
+        assert!(edl.trusted[0].is_public);
+        assert!(!edl.trusted[2].is_public);
+
+        assert_eq!(edl.untrusted.len(), 1);
+        assert_eq!(edl.untrusted[0].name, "ocall_print");
+        assert_eq!(edl.untrusted[0].params.len(), 1);
+    }
+
+    #[test]
+    fn parse_params() {
+        let edl = Edl::parse(ENCLAVE).unwrap();
+        let run = &edl.trusted[0];
+        let names: Vec<_> = run.params.iter().map(|param| param.name.as_str()).collect();
+        assert_eq!(names, ["ptr", "buf", "len"]);
+        assert_eq!(run.params[1].attributes, ["in", "size=len"]);
+        assert_eq!(run.params[1].ty, "const uint8_t *");
+        assert_eq!(run.user_check_params(), ["ptr"]);
+
+        // A sole `void` is an empty parameter list
+        assert!(edl.trusted[1].params.is_empty());
+        // Array dimensions follow the parameter name
+        assert_eq!(edl.trusted[2].params[0].name, "values");
+        assert!(edl.trusted[2].user_check_params().is_empty());
+    }
+
+    #[test]
+    fn follow_imports() {
+        let dir = std::env::temp_dir().join(format!("taurus-edl-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("enclave.edl"),
+            r#"enclave {
+                from "common.edl" import ecall_common;
+                trusted { public void ecall_main(void); };
+            };"#,
+        )
+        .unwrap();
+        // Imports back the importing file
+        std::fs::write(
+            dir.join("common.edl"),
+            r#"enclave {
+                from "enclave.edl" import *;
+                from "sgx_tstd.edl" import *;
+                trusted {
+                    public void ecall_common(void);
+                    public void ecall_unused(void);
+                };
+            };"#,
+        )
+        .unwrap();
+
+        let edl = Edl::from_file(&dir.join("enclave.edl"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let trusted: Vec<_> = edl
+            .unwrap()
+            .trusted
+            .into_iter()
+            .map(|func| func.name)
+            .collect();
+        assert_eq!(trusted, ["ecall_main", "ecall_common"]);
+    }
+}
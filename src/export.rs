@@ -0,0 +1,469 @@
+// Export (parts of) the dependency graph for visualization. The graph can be
+// restricted to the functions between entry points and sensitive functions or
+// to the neighborhood of a function, and functions can be collapsed into their
+// modules or crates.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use petgraph::stable_graph::NodeIndex;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use petgraph::Direction;
+
+use crate::analyzer::{DepGraph, TaurusAnalyzer};
+use crate::query::{crate_name, display_name, matches_pattern};
+use crate::summaries::{EdgeKind, SourceLocation};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Collapse {
+    Function,
+    Module,
+    Crate,
+}
+
+pub struct ExportOptions {
+    // Only keep functions on paths from `from` to `to`. Entry points and
+    // sensitive functions are used if the patterns are not given
+    pub between: bool,
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+    // Only keep functions within a number of calls from a function
+    pub focus: Option<(String, usize)>,
+    pub collapse: Collapse,
+    pub edge_labels: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            between: false,
+            from: Vec::new(),
+            to: Vec::new(),
+            focus: None,
+            collapse: Collapse::Function,
+            edge_labels: false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ExportNode {
+    pub name: String,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub is_entry_point: bool,
+    pub is_lang_item: bool,
+    pub require_audit: Option<String>,
+    pub audited: Option<String>,
+    // Number of functions collapsed into the node
+    pub size: usize,
+}
+
+#[derive(Serialize)]
+pub struct ExportEdge {
+    pub source: usize,
+    pub target: usize,
+    // Location of the first call site
+    pub src_loc: SourceLocation,
+    // Kind of the first call site
+    pub kind: EdgeKind,
+    // Number of call sites collapsed into the edge
+    pub count: usize,
+}
+
+#[derive(Default)]
+pub struct ExportGraph {
+    pub nodes: Vec<ExportNode>,
+    pub edges: Vec<ExportEdge>,
+}
+
+// The module a function belongs to, dropping impl blocks and closures
+fn module_name(name: &str) -> String {
+    let display = display_name(name);
+    let mut components: Vec<&str> = display.split("::").collect();
+    if components.len() > 1 {
+        components.pop();
+    }
+    while components.len() > 1 {
+        match components[components.len() - 1] {
+            "impl" | "impl_trait" | "closure" | "const" | "misc" | "ctor" => components.pop(),
+            _ => break,
+        };
+    }
+    components.join("::")
+}
+
+fn group_name(name: &str, collapse: Collapse) -> String {
+    match collapse {
+        Collapse::Function => name.to_string(),
+        Collapse::Module => module_name(name),
+        Collapse::Crate => crate_name(name),
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Mermaid interprets HTML in labels, and quotes end the labels
+fn escape_mermaid(s: &str) -> String {
+    s.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+fn reachable(dg: &DepGraph, start: &[NodeIndex], direction: Direction) -> HashSet<NodeIndex> {
+    let mut ret = HashSet::new();
+    let mut worklist = start.to_vec();
+    while let Some(node) = worklist.pop() {
+        if ret.insert(node) {
+            worklist.extend(dg.neighbors_directed(node, direction));
+        }
+    }
+    ret
+}
+
+fn find(dg: &DepGraph, patterns: &[String]) -> Vec<NodeIndex> {
+    dg.node_indices()
+        .filter(|&idx| {
+            let name = dg.node_weight(idx).unwrap();
+            patterns
+                .iter()
+                .any(|pattern| matches_pattern(name, pattern))
+        })
+        .collect()
+}
+
+fn select_nodes(
+    analyzer: &TaurusAnalyzer,
+    dg: &DepGraph,
+    entry_points: &HashSet<NodeIndex>,
+    options: &ExportOptions,
+) -> HashSet<NodeIndex> {
+    let mut selected: HashSet<NodeIndex> = dg.node_indices().collect();
+
+    if options.between || !options.from.is_empty() || !options.to.is_empty() {
+        let sources = if options.from.is_empty() {
+            entry_points.iter().cloned().collect()
+        } else {
+            find(dg, &options.from)
+        };
+        let targets: Vec<NodeIndex> = if options.to.is_empty() {
+            dg.node_indices()
+                .filter(|&idx| {
+                    analyzer
+                        .get_marking(dg.node_weight(idx).unwrap())
+                        .map_or(false, |marking| marking.require_audit.is_some())
+                })
+                .collect()
+        } else {
+            find(dg, &options.to)
+        };
+
+        let forward = reachable(dg, &sources, Direction::Outgoing);
+        let backward = reachable(dg, &targets, Direction::Incoming);
+        selected.retain(|node| forward.contains(node) && backward.contains(node));
+    }
+
+    if let Some((focus, depth)) = &options.focus {
+        let mut distance = HashMap::new();
+        let mut queue = VecDeque::new();
+        for node in find(dg, &[focus.to_string()]) {
+            distance.insert(node, 0);
+            queue.push_back(node);
+        }
+        while let Some(node) = queue.pop_front() {
+            let d = distance[&node];
+            if d < *depth {
+                for neighbor in dg.neighbors_undirected(node) {
+                    if !distance.contains_key(&neighbor) {
+                        distance.insert(neighbor, d + 1);
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+        selected.retain(|node| distance.contains_key(node));
+    }
+
+    selected
+}
+
+impl ExportGraph {
+    pub fn new(analyzer: &TaurusAnalyzer, options: &ExportOptions) -> Self {
+        let (dg, entry_points) = analyzer.get_depgraph();
+        let selected = select_nodes(analyzer, &dg, &entry_points, options);
+
+        let mut ret = ExportGraph::default();
+        let mut groups = HashMap::<String, usize>::new();
+        let mut node_group = HashMap::<NodeIndex, usize>::new();
+
+        for node in dg.node_indices().filter(|node| selected.contains(node)) {
+            let name = dg.node_weight(node).unwrap();
+            let group = group_name(name, options.collapse);
+            let idx = *groups.entry(group.clone()).or_insert_with(|| {
+                ret.nodes.push(ExportNode {
+                    krate: crate_name(&group),
+                    name: group,
+                    is_entry_point: false,
+                    is_lang_item: false,
+                    require_audit: None,
+                    audited: None,
+                    size: 0,
+                });
+                ret.nodes.len() - 1
+            });
+
+            let export_node = &mut ret.nodes[idx];
+            export_node.size += 1;
+            export_node.is_entry_point |= entry_points.contains(&node);
+            export_node.is_lang_item |= analyzer.is_lang_item(name);
+            if let Some(marking) = analyzer.get_marking(name) {
+                if export_node.require_audit.is_none() {
+                    export_node.require_audit = marking.require_audit;
+                }
+                if export_node.audited.is_none() {
+                    export_node.audited = marking.audited;
+                }
+            }
+            node_group.insert(node, idx);
+        }
+
+        let mut collapsed_edges = HashMap::<(usize, usize), usize>::new();
+        for edge in dg.edge_references() {
+            if let (Some(&source), Some(&target)) = (
+                node_group.get(&edge.source()),
+                node_group.get(&edge.target()),
+            ) {
+                if options.collapse == Collapse::Function {
+                    ret.edges.push(ExportEdge {
+                        source,
+                        target,
+                        src_loc: edge.weight().src_loc.clone(),
+                        kind: edge.weight().kind,
+                        count: 1,
+                    });
+                } else if source != target {
+                    match collapsed_edges.get(&(source, target)) {
+                        Some(&idx) => ret.edges[idx].count += 1,
+                        None => {
+                            collapsed_edges.insert((source, target), ret.edges.len());
+                            ret.edges.push(ExportEdge {
+                                source,
+                                target,
+                                src_loc: edge.weight().src_loc.clone(),
+                                kind: edge.weight().kind,
+                                count: 1,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        ret
+    }
+
+    pub fn to_dot(&self, edge_labels: bool) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut ret = String::from("digraph {\n");
+
+        for (idx, node) in self.nodes.iter().enumerate() {
+            // Entry points are green, sensitive functions red, and auditors blue
+            let color = if node.is_entry_point {
+                Some("green")
+            } else if node.require_audit.is_some() {
+                Some("red")
+            } else if node.audited.is_some() {
+                Some("blue")
+            } else {
+                None
+            };
+
+            ret.push_str(&format!("    {} [ label = \"{}\"", idx, escape(&node.name)));
+            if let Some(color) = color {
+                ret.push_str(&format!(" color = \"{}\"", color));
+            }
+            ret.push_str(" ]\n");
+        }
+
+        for edge in &self.edges {
+            ret.push_str(&format!("    {} -> {}", edge.source, edge.target));
+            let mut attrs = Vec::new();
+            if edge_labels {
+                let mut label = edge.src_loc.to_string();
+                if edge.kind != EdgeKind::Call {
+                    label.push_str(&format!(" [{}]", edge.kind));
+                }
+                if edge.count > 1 {
+                    label.push_str(&format!(" (+{})", edge.count - 1));
+                }
+                attrs.push(format!("label = \"{}\"", escape(&label)));
+            }
+            // Edges other than plain calls are dashed
+            if edge.kind != EdgeKind::Call {
+                attrs.push("style = \"dashed\"".to_string());
+            }
+            if !attrs.is_empty() {
+                ret.push_str(&format!(" [ {} ]", attrs.join(" ")));
+            }
+            ret.push('\n');
+        }
+
+        ret.push_str("}\n");
+        ret
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut ret = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        );
+
+        let keys = [
+            ("name", "node", "string"),
+            ("crate", "node", "string"),
+            ("require_audit", "node", "string"),
+            ("audited", "node", "string"),
+            ("is_entry_point", "node", "boolean"),
+            ("is_lang_item", "node", "boolean"),
+            ("size", "node", "int"),
+            ("location", "edge", "string"),
+            ("kind", "edge", "string"),
+            ("count", "edge", "int"),
+        ];
+        for (name, domain, ty) in keys.iter() {
+            ret.push_str(&format!(
+                "  <key id=\"{0}\" for=\"{1}\" attr.name=\"{0}\" attr.type=\"{2}\"/>\n",
+                name, domain, ty
+            ));
+        }
+
+        ret.push_str("  <graph edgedefault=\"directed\">\n");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            ret.push_str(&format!("    <node id=\"n{}\">\n", idx));
+            let mut data = vec![
+                ("name", node.name.clone()),
+                ("crate", node.krate.clone()),
+                ("is_entry_point", node.is_entry_point.to_string()),
+                ("is_lang_item", node.is_lang_item.to_string()),
+                ("size", node.size.to_string()),
+            ];
+            if let Some(meta) = &node.require_audit {
+                data.push(("require_audit", meta.clone()));
+            }
+            if let Some(meta) = &node.audited {
+                data.push(("audited", meta.clone()));
+            }
+            for (key, value) in data {
+                ret.push_str(&format!(
+                    "      <data key=\"{}\">{}</data>\n",
+                    key,
+                    escape_xml(&value)
+                ));
+            }
+            ret.push_str("    </node>\n");
+        }
+
+        for (idx, edge) in self.edges.iter().enumerate() {
+            ret.push_str(&format!(
+                "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\">\n",
+                idx, edge.source, edge.target
+            ));
+            ret.push_str(&format!(
+                "      <data key=\"location\">{}</data>\n",
+                escape_xml(&edge.src_loc.to_string())
+            ));
+            ret.push_str(&format!("      <data key=\"kind\">{}</data>\n", edge.kind));
+            ret.push_str(&format!(
+                "      <data key=\"count\">{}</data>\n",
+                edge.count
+            ));
+            ret.push_str("    </edge>\n");
+        }
+
+        ret.push_str("  </graph>\n</graphml>\n");
+        ret
+    }
+
+    /// Export in the node-link JSON format, as used by d3 and networkx
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| {
+                let mut value = serde_json::to_value(node).unwrap();
+                value["id"] = serde_json::Value::from(idx);
+                value
+            })
+            .collect();
+
+        let graph = serde_json::json!({
+            "directed": true,
+            "multigraph": true,
+            "graph": {},
+            "nodes": nodes,
+            "links": self.edges,
+        });
+
+        serde_json::to_string_pretty(&graph).unwrap()
+    }
+
+    pub fn to_mermaid(&self, edge_labels: bool) -> String {
+        let mut ret = String::from("flowchart LR\n");
+
+        for (idx, node) in self.nodes.iter().enumerate() {
+            ret.push_str(&format!(
+                "    n{}[\"{}\"]\n",
+                idx,
+                escape_mermaid(&node.name)
+            ));
+        }
+
+        for edge in &self.edges {
+            // Edges other than plain calls are dashed, as in the DOT export
+            let arrow = if edge.kind == EdgeKind::Call {
+                "-->"
+            } else {
+                "-.->"
+            };
+            if edge_labels {
+                let mut label = edge.src_loc.to_string();
+                if edge.kind != EdgeKind::Call {
+                    label.push_str(&format!(" [{}]", edge.kind));
+                }
+                ret.push_str(&format!(
+                    "    n{} {}|\"{}\"| n{}\n",
+                    edge.source,
+                    arrow,
+                    escape_mermaid(&label),
+                    edge.target
+                ));
+            } else {
+                ret.push_str(&format!(
+                    "    n{} {} n{}\n",
+                    edge.source, arrow, edge.target
+                ));
+            }
+        }
+
+        // Same colors as in the DOT export
+        ret.push_str("    classDef entry stroke:green\n");
+        ret.push_str("    classDef sink stroke:red\n");
+        ret.push_str("    classDef auditor stroke:blue\n");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if node.is_entry_point {
+                ret.push_str(&format!("    class n{} entry\n", idx));
+            } else if node.require_audit.is_some() {
+                ret.push_str(&format!("    class n{} sink\n", idx));
+            } else if node.audited.is_some() {
+                ret.push_str(&format!("    class n{} auditor\n", idx));
+            }
+        }
+
+        ret
+    }
+}
@@ -1,17 +1,28 @@
 extern crate seahash;
 
-use rustc::hir::def_id::DefId;
+use rustc::hir::def_id::{DefId, LOCAL_CRATE};
+use rustc::mir::interpret::PanicInfo;
 use rustc::mir::mono::MonoItem;
+use rustc::mir::visit::PlaceContext;
 use rustc::mir::visit::Visitor;
-use rustc::mir::{Body, Location, Operand, Terminator, TerminatorKind};
+use rustc::mir::{
+    Body, Location, Operand, Place, PlaceBase, Statement, StatementKind, StaticKind, Terminator,
+    TerminatorKind,
+};
+use rustc::session::config::host_triple;
+use rustc::ty::subst::SubstsRef;
 use rustc::ty::{Instance, InstanceDef, TyCtxt, TyKind};
 use rustc_interface::interface;
 use rustc_mir::monomorphize::collector::{collect_crate_mono_items, MonoItemCollectionMode};
+use rustc_target::spec::abi::Abi;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 use crate::annotated::*;
+use crate::constant_time::ConstantTimeChecker;
+use crate::edl::Edl;
+use crate::paths::PathNormalizer;
 use crate::summaries::*;
 
 struct MirScanner<'a, 'tcx: 'a> {
@@ -21,30 +32,129 @@ struct MirScanner<'a, 'tcx: 'a> {
     pub is_local: bool,
     pub body: &'a Body<'tcx>,
     pub is_lang_item: bool,
+    pub paths: &'a PathNormalizer,
+}
+
+// The kind of panic started by calling `def_id`, if it starts one
+fn panic_kind(tcx: &TyCtxt<'_>, def_id: DefId) -> Option<&'static str> {
+    let lang_items = tcx.lang_items();
+    if Some(def_id) == lang_items.panic_fn() || Some(def_id) == lang_items.begin_panic_fn() {
+        return Some("explicit");
+    }
+    if Some(def_id) == lang_items.panic_bounds_check_fn() {
+        return Some("bounds_check");
+    }
+
+    match tcx.def_path_str(def_id).as_str() {
+        "core::panicking::panic_fmt" | "std::panicking::begin_panic_fmt" => Some("explicit"),
+        "core::option::expect_failed" | "core::result::unwrap_failed" => Some("unwrap"),
+        _ => None,
+    }
+}
+
+// The category of calls to `def_id`, if it is a sensitive intrinsic. Intrinsics
+// of LLVM are declared as foreign functions linked to `llvm.*`
+fn intrinsic_category(tcx: &TyCtxt<'_>, def_id: DefId) -> Option<&'static str> {
+    let name = tcx.item_name(def_id).as_str();
+    match tcx.fn_sig(def_id).abi() {
+        Abi::PlatformIntrinsic => Some(INTRINSIC_SIMD_CATEGORY),
+        Abi::RustIntrinsic if name.starts_with("volatile_") => Some(INTRINSIC_VOLATILE_CATEGORY),
+        Abi::RustIntrinsic if name.starts_with("simd_") => Some(INTRINSIC_SIMD_CATEGORY),
+        Abi::RustIntrinsic => None,
+        _ => match tcx.codegen_fn_attrs(def_id).link_name {
+            Some(link_name)
+                if link_name.as_str().starts_with("llvm.x86.rdrand")
+                    || link_name.as_str().starts_with("llvm.x86.rdseed") =>
+            {
+                Some(INTRINSIC_RDRAND_CATEGORY)
+            }
+            Some(link_name) if link_name.as_str().starts_with("llvm.") => {
+                Some(INTRINSIC_LLVM_CATEGORY)
+            }
+            _ => None,
+        },
+    }
+}
+
+// Identifies a call site in the names of pseudo functions. The location is
+// normalized such that names do not depend on the build machine
+fn site_hash(src_loc: &SourceLocation) -> u64 {
+    seahash::hash(src_loc.to_string().as_bytes())
 }
 
 impl<'a, 'tcx: 'a> Visitor<'tcx> for MirScanner<'a, 'tcx> {
+    fn visit_statement(&mut self, statement: &Statement<'tcx>, mir_loc: Location) {
+        if let StatementKind::InlineAsm(..) = statement.kind {
+            let val = self.pseudo_edge(ASM_DEF_NAME_CANONICAL.to_string(), mir_loc);
+            self.result.push(val);
+        }
+
+        self.super_statement(statement, mir_loc);
+    }
+
     fn visit_terminator(&mut self, term: &Terminator<'tcx>, mir_loc: Location) {
+        if let TerminatorKind::Assert { msg, .. } = &term.kind {
+            // Checks inserted by the compiler. Those in other crates are
+            // covered by the panics of their public functions
+            if self.is_local {
+                let kind = match msg {
+                    PanicInfo::BoundsCheck { .. } => "bounds_check",
+                    PanicInfo::Overflow(..) | PanicInfo::OverflowNeg => "overflow",
+                    PanicInfo::DivisionByZero | PanicInfo::RemainderByZero => "division_by_zero",
+                    _ => "assert",
+                };
+                let val = self.panic_edge(kind, mir_loc);
+                self.result.push(val);
+            }
+        }
+
         if let TerminatorKind::Call { func, .. } = &term.kind {
-            if let TyKind::FnPtr(..) = func.ty(self.body, *self.canonical.tcx()).kind {
-                let loc = self
-                    .canonical
-                    .source_map()
-                    .lookup_char_pos(self.body.source_info(mir_loc).span.lo());
-                let src_loc_pretty = format!("{:#?}", loc);
-                let encoded = seahash::hash(&src_loc_pretty.as_bytes());
+            let func_ty = func.ty(self.body, *self.canonical.tcx());
+            if let TyKind::FnDef(callee_def_id, _) = func_ty.kind {
+                if let Some(kind) = panic_kind(self.canonical.tcx(), callee_def_id) {
+                    let val = self.panic_edge(kind, mir_loc);
+                    self.result.push(val);
+                }
+                // Each call to an intrinsic is a sensitive site on its own
+                if let Some(category) = intrinsic_category(self.canonical.tcx(), callee_def_id) {
+                    let name = self.canonical.tcx().item_name(callee_def_id);
+                    let val = self.pseudo_edge(format!("@{}::{}", category, name), mir_loc);
+                    self.result.push(val);
+                }
+            }
 
+            if let TyKind::FnPtr(..) = func_ty.kind {
+                let src_loc = self.src_loc(mir_loc);
                 let val = DepEdge {
-                    callee_def: format!("{}#{}", FNPTR_DEF_NAME_CANONICAL, encoded),
+                    callee_def: format!("{}#{}", FNPTR_DEF_NAME_CANONICAL, site_hash(&src_loc)),
+                    trait_item: None,
                     is_lang_item: self.is_lang_item,
                     type_params: Vec::new(),
-                    src_loc: (&loc).into(),
+                    src_loc,
+                    kind: EdgeKind::FnPtr,
                 };
 
                 self.result.push(val);
             }
         }
 
+        // Destructors are called implicitly, so they do not show up as
+        // operands. Only the destructor of the dropped type itself is recorded,
+        // not those of its fields
+        match &term.kind {
+            TerminatorKind::Drop { location, .. }
+            | TerminatorKind::DropAndReplace { location, .. } => {
+                let tcx = *self.canonical.tcx();
+                if let TyKind::Adt(adt_def, substs) = location.ty(self.body, tcx).ty.kind {
+                    if let Some(destructor) = tcx.adt_destructor(adt_def.did) {
+                        let val = self.call_edge(destructor.did, substs, mir_loc, EdgeKind::Drop);
+                        self.result.push(val);
+                    }
+                }
+            }
+            _ => (),
+        }
+
         self.super_terminator(term, mir_loc);
     }
 
@@ -54,6 +164,15 @@ impl<'a, 'tcx: 'a> Visitor<'tcx> for MirScanner<'a, 'tcx> {
         {
             let mut def_id = callee_def_id;
             let mut generic_args = substs;
+            let mut kind = EdgeKind::Call;
+
+            // Calls of trait methods on trait objects are dispatched through
+            // the vtable, wherever the trait and its implementations are
+            if self.canonical.tcx().trait_of_item(def_id).is_some() {
+                if let TyKind::Dynamic(..) = substs.type_at(0).kind {
+                    kind = EdgeKind::Virtual;
+                }
+            }
 
             if !self.canonical.tcx().is_mir_available(def_id) {
                 // We can only resolve trait functions for local crates. rustc may
@@ -76,37 +195,93 @@ impl<'a, 'tcx: 'a> Visitor<'tcx> for MirScanner<'a, 'tcx> {
                 }
             }
 
-            let loc = self
-                .canonical
-                .source_map()
-                .lookup_char_pos(self.body.source_info(mir_loc).span.lo());
-
-            let type_params: Vec<String> = generic_args
-                .types()
-                .into_iter()
-                .map(|ty| self.canonical.normalized_type_name(ty))
-                .collect();
-
-            let val = DepEdge {
-                callee_def: self.canonical.def_name(def_id),
-                is_lang_item: self.is_lang_item,
-                type_params,
-                src_loc: (&loc).into(),
-            };
+            if self.canonical.tcx().is_closure(def_id) {
+                kind = EdgeKind::Closure;
+            }
 
+            let val = self.call_edge(def_id, generic_args, mir_loc, kind);
             self.result.push(val);
         }
 
         self.super_operand(operand, mir_loc);
     }
+
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, mir_loc: Location) {
+        if let PlaceBase::Static(static_) = &place.base {
+            // Promoted constants are not shared state
+            if let StaticKind::Static = static_.kind {
+                self.result.push(DepEdge {
+                    callee_def: self.canonical.def_name(static_.def_id),
+                    trait_item: None,
+                    is_lang_item: self.is_lang_item,
+                    type_params: Vec::new(),
+                    src_loc: self.src_loc(mir_loc),
+                    kind: EdgeKind::Static,
+                });
+            }
+        }
+
+        self.super_place(place, context, mir_loc);
+    }
 }
 
 impl<'a, 'tcx: 'a> MirScanner<'a, 'tcx> {
+    fn src_loc(&self, mir_loc: Location) -> SourceLocation {
+        SourceLocation::from_span(
+            self.canonical.source_map(),
+            self.body.source_info(mir_loc).span,
+            self.paths,
+        )
+    }
+
+    fn call_edge(
+        &self,
+        def_id: DefId,
+        generic_args: SubstsRef<'tcx>,
+        mir_loc: Location,
+        kind: EdgeKind,
+    ) -> DepEdge {
+        let type_params: Vec<String> = generic_args
+            .types()
+            .into_iter()
+            .map(|ty| self.canonical.normalized_type_name(ty))
+            .collect();
+
+        DepEdge {
+            callee_def: self.canonical.def_name(def_id),
+            trait_item: self.canonical.implemented_trait_item(def_id),
+            is_lang_item: self.is_lang_item,
+            type_params,
+            src_loc: self.src_loc(mir_loc),
+            kind,
+        }
+    }
+
+    // An edge to a pseudo function standing for the panic site at `mir_loc`
+    fn panic_edge(&self, kind: &str, mir_loc: Location) -> DepEdge {
+        self.pseudo_edge(format!("{}::{}", PANIC_DEF_NAME_CANONICAL, kind), mir_loc)
+    }
+
+    // An edge to a pseudo function standing for the sensitive operation at
+    // `mir_loc`. Each site has its own function, named after its location
+    fn pseudo_edge(&self, name: String, mir_loc: Location) -> DepEdge {
+        let src_loc = self.src_loc(mir_loc);
+        DepEdge {
+            callee_def: format!("{}#{}", name, site_hash(&src_loc)),
+            trait_item: None,
+            is_lang_item: self.is_lang_item,
+            type_params: Vec::new(),
+            src_loc,
+            kind: EdgeKind::Call,
+        }
+    }
+
     pub fn scan(
         def_id: DefId,
         mir_body: &'a Body<'tcx>,
         canonical: &'a Canonical<'tcx, 'a>,
         is_lang_item: bool,
+        paths: &'a PathNormalizer,
     ) -> Vec<DepEdge> {
         let is_local = canonical.tcx().hir().as_local_hir_id(def_id).is_some();
         let mut mir_scanner = MirScanner {
@@ -116,6 +291,7 @@ impl<'a, 'tcx: 'a> MirScanner<'a, 'tcx> {
             is_local,
             body: &mir_body,
             is_lang_item,
+            paths,
         };
 
         mir_scanner.visit_body(mir_body);
@@ -128,6 +304,13 @@ pub struct TaurusExtractor {
     file_name: String,
     output_dir: PathBuf,
     lang_items: HashSet<DefId>,
+    // Treat functions following TEE conventions as entry points
+    discover_entry_points: bool,
+    // EDL files describing the ECALLs of the enclave
+    edl_files: Vec<PathBuf>,
+    // Source paths are recorded relative to the workspace root
+    workspace_root: PathBuf,
+    working_dir: PathBuf,
 }
 
 impl Default for TaurusExtractor {
@@ -136,6 +319,10 @@ impl Default for TaurusExtractor {
             file_name: String::new(),
             output_dir: PathBuf::default(),
             lang_items: HashSet::new(),
+            discover_entry_points: false,
+            edl_files: Vec::new(),
+            workspace_root: PathBuf::default(),
+            working_dir: PathBuf::default(),
         }
     }
 }
@@ -152,6 +339,21 @@ impl rustc_driver::Callbacks for TaurusExtractor {
             }
             Some(path_buf) => self.output_dir.push(path_buf.as_path()),
         };
+
+        // Cargo does not forward extra arguments to RUSTC_WRAPPER, so the
+        // extractor is configured through the environment
+        self.discover_entry_points = std::env::var("TAURUS_DISCOVER_ENTRY_POINTS").is_ok();
+        if let Some(paths) = std::env::var_os("TAURUS_EDL") {
+            self.edl_files = std::env::split_paths(&paths).collect();
+        }
+        // Cargo runs rustc from the workspace root
+        self.working_dir = std::env::current_dir().expect("failed to read the working directory");
+        // A relative root is taken from the working directory, while joining
+        // an absolute one replaces the working directory
+        self.workspace_root = std::env::var_os("TAURUS_WORKSPACE_ROOT").map_or_else(
+            || self.working_dir.clone(),
+            |root| self.working_dir.join(root),
+        );
     }
 
     /// Called after the compiler has completed all analysis passes and before
@@ -194,6 +396,7 @@ impl TaurusExtractor {
         &mut self,
         canonical: &Canonical<'tcx, '_>,
         mono_instance: &Instance<'tcx>,
+        paths: &PathNormalizer,
     ) -> (String, Vec<DepEdge>) {
         let tcx = canonical.tcx();
 
@@ -212,7 +415,7 @@ impl TaurusExtractor {
         let def_id = mono_instance.def.def_id();
         let mir = tcx.instance_mir(mono_instance.def);
 
-        let call_edges = MirScanner::scan(def_id, mir, canonical, is_lang_item);
+        let call_edges = MirScanner::scan(def_id, mir, canonical, is_lang_item, paths);
 
         (
             canonical.monoitem_name(mono_instance.def.def_id(), mono_instance.substs),
@@ -236,34 +439,84 @@ impl TaurusExtractor {
                 .expect("failed to access consistent storage");
 
         let hir_map = tcx.hir();
-        let annotated_funcs = extract_annotated_functions(&tcx);
+        let mut annotated_funcs = extract_annotated_functions(&tcx);
 
-        let canonical = Canonical::new(&tcx, compiler.source_map().clone());
+        let mut constant_time_checker = ConstantTimeChecker::new(tcx);
+        for (def_id, secret_params) in extract_constant_time_functions(&tcx) {
+            constant_time_checker.check(def_id, &secret_params);
+        }
 
-        for (hir_id, marking) in annotated_funcs {
-            let def_id = hir_map.local_def_id(hir_id);
-            let name = canonical.def_name(def_id);
-            let span = tcx.def_span(def_id);
-            let src_loc = canonical.source_map().lookup_char_pos(span.lo());
-
-            marking_db.insert(
-                name,
-                MarkedItem {
-                    marking,
-                    src_loc: (&src_loc).into(),
-                },
+        let mut ecalls = HashMap::new();
+        let mut ocalls = HashSet::new();
+        for edl_file in &self.edl_files {
+            let edl = Edl::from_file(edl_file).unwrap_or_else(|e| tcx.sess.fatal(&e));
+            for func in edl.trusted {
+                ecalls.insert(func.name.clone(), func.user_check_params());
+            }
+            ocalls.extend(edl.untrusted.into_iter().map(|func| func.name));
+        }
+
+        if !ocalls.is_empty() {
+            mark_ocalls(&tcx, &mut annotated_funcs, &ocalls);
+        }
+
+        if self.discover_entry_points || !ecalls.is_empty() {
+            discover_entry_points(
+                &tcx,
+                &mut annotated_funcs,
+                &ecalls,
+                self.discover_entry_points,
             );
         }
 
+        if self.discover_entry_points {
+            report_uncovered_exports(&tcx, &annotated_funcs);
+        }
+
+        // Categories are validated by the analyzer, once the categories
+        // declared by all crates are known. Recompiling a crate replaces the
+        // categories it declared before. The library and the binaries of a
+        // package share their names and differ in their disambiguators
+        let mut category_db = PersistentSummaryStore::<Vec<String>>::new(&db_path.join("category"))
+            .expect("failed to access consistent storage");
+        category_db.insert(
+            format!(
+                "{}-{}",
+                tcx.crate_name(LOCAL_CRATE),
+                tcx.crate_disambiguator(LOCAL_CRATE)
+            ),
+            extract_categories(&tcx),
+        );
+
+        let canonical = Canonical::new(&tcx, compiler.source_map().clone());
+        // The toolchain versions the files of the standard library. Drivers
+        // cannot query the release of the compiler, so the toolchain taurus is
+        // built with is recorded, without the host triple
+        let rustc_version = option_env!("RUSTUP_TOOLCHAIN")
+            .unwrap_or("unknown")
+            .trim_end_matches(host_triple())
+            .trim_end_matches('-');
+        let paths = PathNormalizer::new(&self.workspace_root, &self.working_dir, rustc_version);
+
         let (mono_items, _) = collect_crate_mono_items(tcx, MonoItemCollectionMode::Eager);
 
         for mi in mono_items {
             if let MonoItem::Fn(inst) = mi {
                 if let InstanceDef::Item(_) = inst.def {
-                    let (caller_name, call_edges) = self.collect_call_edges(&canonical, &inst);
+                    let (caller_name, call_edges) =
+                        self.collect_call_edges(&canonical, &inst, &paths);
                     calledge_db.insert(caller_name, call_edges);
                 }
             }
         }
+
+        for (hir_id, marking) in annotated_funcs {
+            let def_id = hir_map.local_def_id(hir_id);
+            let name = canonical.def_name(def_id);
+            let src_loc =
+                SourceLocation::from_span(canonical.source_map(), tcx.def_span(def_id), &paths);
+
+            marking_db.insert(name, MarkedItem { marking, src_loc });
+        }
     }
 }
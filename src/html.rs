@@ -0,0 +1,203 @@
+// Generate a static HTML report from an audit, for auditors who do not work
+// with Rust tooling. The report consists of an index page with statistics and
+// one page per entry point. Pages do not refer to any external assets, so the
+// report can be archived as is.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::analyzer::{AuditReport, Finding};
+use crate::summaries::SourceLocation;
+
+// Number of lines shown before and after a call site
+const SNIPPET_CONTEXT: usize = 3;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+code, pre { font-family: monospace; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; }
+.highlight { background: #ffe08a; display: block; }
+.unaudited { color: #b00020; }
+.audited { color: #006400; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+details { margin: 0.5em 0; }
+li { margin: 0.3em 0; }";
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{0}</title>\n<style>\n{1}\n</style>\n</head>\n\
+         <body>\n<h1>{0}</h1>\n{2}</body>\n</html>\n",
+        escape_html(title),
+        STYLE,
+        body
+    )
+}
+
+fn entry_page_name(idx: usize) -> String {
+    format!("entry-{}.html", idx)
+}
+
+// Read source files lazily, remembering files that cannot be read
+struct SourceCache {
+    files: HashMap<String, Option<Vec<String>>>,
+}
+
+impl SourceCache {
+    fn snippet(&mut self, src_loc: &SourceLocation) -> String {
+        let lines = self.files.entry(src_loc.file.clone()).or_insert_with(|| {
+            std::fs::read_to_string(&src_loc.file)
+                .ok()
+                .map(|src| src.lines().map(String::from).collect())
+        });
+
+        match lines {
+            Some(lines) if src_loc.line_no > 0 && src_loc.line_no <= lines.len() => {
+                let first = src_loc.line_no.saturating_sub(SNIPPET_CONTEXT).max(1);
+                let last = (src_loc.line_no + SNIPPET_CONTEXT).min(lines.len());
+                let mut ret = String::from("<pre>");
+                for line_no in first..=last {
+                    let line = format!("{:>5} | {}", line_no, escape_html(&lines[line_no - 1]));
+                    if line_no == src_loc.line_no {
+                        ret.push_str(&format!("<span class=\"highlight\">{}</span>", line));
+                    } else {
+                        ret.push_str(&line);
+                        ret.push('\n');
+                    }
+                }
+                ret.push_str("</pre>\n");
+                ret
+            }
+            _ => "<p><em>source not available</em></p>\n".to_string(),
+        }
+    }
+}
+
+fn render_finding(finding: &Finding, auditor: Option<&str>, sources: &mut SourceCache) -> String {
+    let (class, status) = match auditor {
+        Some(auditor) => (
+            "audited",
+            format!("audited by <code>{}</code>", escape_html(auditor)),
+        ),
+        None => ("unaudited", "unaudited".to_string()),
+    };
+
+    let mut ret = format!(
+        "<details>\n<summary><span class=\"{}\">[{}]</span> <code>{}</code> ({})",
+        class,
+        escape_html(&finding.key.category),
+        escape_html(&finding.key.sink),
+        status
+    );
+    if finding.alternatives > 0 {
+        ret.push_str(&format!(", {} alternative paths", finding.alternatives));
+    }
+    ret.push_str("</summary>\n<ol>\n");
+
+    for hop in finding.witness.hops() {
+        ret.push_str(&format!(
+            "<li><code>{}</code> at {} ({})\n{}</li>\n",
+            escape_html(&hop.callee),
+            escape_html(&hop.src_loc.to_string()),
+            hop.kind,
+            sources.snippet(&hop.src_loc)
+        ));
+    }
+
+    ret.push_str("</ol>\n</details>\n");
+    ret
+}
+
+// Number of unaudited and audited findings per category
+fn category_stats(report: &AuditReport) -> BTreeMap<&str, (usize, usize)> {
+    let mut ret = BTreeMap::new();
+    for finding in &report.unaudited {
+        ret.entry(finding.key.category.as_str()).or_insert((0, 0)).0 += 1;
+    }
+    for (_, finding) in &report.audited {
+        ret.entry(finding.key.category.as_str()).or_insert((0, 0)).1 += 1;
+    }
+    ret
+}
+
+pub fn write_html_report(report: &AuditReport, out_dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(out_dir)?;
+    let mut sources = SourceCache {
+        files: HashMap::new(),
+    };
+
+    let mut index = String::from("<h2>Summary</h2>\n<table>\n");
+    index.push_str("<tr><th>Category</th><th>Unaudited</th><th>Audited</th></tr>\n");
+    for (category, (unaudited, audited)) in category_stats(report) {
+        index.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(category),
+            unaudited,
+            audited
+        ));
+    }
+    index.push_str("</table>\n<h2>Entry points</h2>\n<ul>\n");
+
+    for (idx, entry_point) in report.entry_points.iter().enumerate() {
+        let unaudited: Vec<&Finding> = report
+            .unaudited
+            .iter()
+            .filter(|finding| &finding.key.entry_point == entry_point)
+            .collect();
+        let audited: Vec<&(String, Finding)> = report
+            .audited
+            .iter()
+            .filter(|(_, finding)| &finding.key.entry_point == entry_point)
+            .collect();
+
+        index.push_str(&format!(
+            "<li><a href=\"{}\"><code>{}</code></a>: {} unaudited, {} audited</li>\n",
+            entry_page_name(idx),
+            escape_html(entry_point),
+            unaudited.len(),
+            audited.len()
+        ));
+
+        let mut body = String::from("<p><a href=\"index.html\">Back to summary</a></p>\n");
+        for (entry, params) in &report.user_check {
+            if &entry.0 == entry_point {
+                body.push_str(&format!(
+                    "<p class=\"unaudited\">Unaudited [user_check] parameters: <code>{}</code></p>\n{}",
+                    escape_html(&params.join(", ")),
+                    sources.snippet(&entry.1)
+                ));
+            }
+        }
+        body.push_str("<h2>Unaudited</h2>\n");
+        for finding in unaudited {
+            body.push_str(&render_finding(finding, None, &mut sources));
+        }
+        body.push_str("<h2>Audited</h2>\n");
+        for (auditor, finding) in audited {
+            body.push_str(&render_finding(finding, Some(auditor.as_str()), &mut sources));
+        }
+
+        std::fs::write(out_dir.join(entry_page_name(idx)), page(entry_point, &body))?;
+    }
+    index.push_str("</ul>\n");
+
+    if !report.resolved.is_empty() {
+        index.push_str("<h2>Resolved since baseline</h2>\n<ul>\n");
+        for key in &report.resolved {
+            index.push_str(&format!("<li>{}</li>\n", escape_html(&key.to_string())));
+        }
+        index.push_str("</ul>\n");
+    }
+
+    std::fs::write(
+        out_dir.join("index.html"),
+        page("Taurus audit report", &index),
+    )
+}
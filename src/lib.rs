@@ -15,6 +15,7 @@ extern crate syntax_pos;
 extern crate log;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 
 #[cfg(feature = "use_sqlite")]
 extern crate rusqlite;
@@ -23,6 +24,16 @@ extern crate sled;
 
 pub mod analyzer;
 pub(crate) mod annotated;
+pub mod baseline;
+pub(crate) mod constant_time;
+pub mod coverage;
+pub mod diff;
+pub(crate) mod edl;
+pub mod export;
 pub mod extractor;
+pub mod html;
+pub mod paths;
+pub mod prune;
+pub mod query;
 pub(crate) mod summaries;
 pub(crate) mod utils;
@@ -3,12 +3,22 @@
 extern crate env_logger;
 extern crate getopts;
 extern crate rustc_driver;
+extern crate serde_json;
 
 use std::env;
+use std::io::BufRead;
 use std::path::Path;
 
 use taurus::analyzer;
+use taurus::baseline;
+use taurus::coverage;
+use taurus::diff;
+use taurus::export;
 use taurus::extractor;
+use taurus::html;
+use taurus::paths;
+use taurus::prune;
+use taurus::query;
 
 // Probe the sysroot for rust compiler. This should be fairly simple if user uses
 // rustup to setup the environment.
@@ -73,18 +83,307 @@ fn main() {
             "print the dependency graph in dot format to stdout",
         );
 
+        opts.optflag(
+            "",
+            "all-paths",
+            "report all simple paths to sensitive functions instead of the first one per edge",
+        );
+        opts.optopt(
+            "",
+            "max-depth",
+            "maximum number of calls in a path reported with --all-paths (default 32)",
+            "N",
+        );
+        opts.optopt(
+            "",
+            "max-paths",
+            "maximum number of paths explored per entry point with --all-paths (default 1000)",
+            "N",
+        );
+        opts.optopt(
+            "",
+            "write-baseline",
+            "record the current findings as the baseline of later audits",
+            "FILE",
+        );
+        opts.optopt(
+            "",
+            "baseline",
+            "only report findings that are not in the baseline, and those that disappeared",
+            "FILE",
+        );
+        opts.optflag("", "json", "print query results and coverage in JSON");
+        opts.optopt(
+            "",
+            "html",
+            "write the audit report as static HTML pages to the directory",
+            "DIR",
+        );
+        opts.optopt(
+            "",
+            "export",
+            "print the dependency graph in the given format to stdout",
+            "dot|graphml|json|mermaid",
+        );
+        opts.optflag(
+            "",
+            "between",
+            "only export functions on paths from entry points to sensitive functions",
+        );
+        opts.optmulti(
+            "",
+            "from",
+            "only export functions reachable from the given functions",
+            "FN",
+        );
+        opts.optmulti(
+            "",
+            "to",
+            "only export functions reaching the given functions",
+            "FN",
+        );
+        opts.optopt(
+            "",
+            "focus",
+            "only export functions within --depth calls of the given function",
+            "FN",
+        );
+        opts.optopt(
+            "",
+            "depth",
+            "number of calls around the function given by --focus (default 2)",
+            "N",
+        );
+        opts.optopt(
+            "",
+            "collapse",
+            "collapse the exported functions into their modules or crates",
+            "module|crate",
+        );
+        opts.optflag(
+            "",
+            "edge-labels",
+            "label exported edges with the locations of call sites",
+        );
+        opts.optflag(
+            "",
+            "callers",
+            "print all callers of each sensitive function reachable from entry points",
+        );
+        opts.optflag(
+            "",
+            "panics",
+            "report panic sites reachable from entry points, unless audited as \"panic\"",
+        );
+        opts.optflag(
+            "",
+            "alloc",
+            "report heap allocations reachable from #[taurus::no_alloc] functions instead",
+        );
+        opts.optopt(
+            "",
+            "edge-kinds",
+            "only follow these kinds of edges to sensitive functions (default all)",
+            "call,virtual,fnptr,drop,static,closure",
+        );
+        opts.optflag(
+            "",
+            "hierarchical-categories",
+            "let auditors also discharge sensitive functions of subcategories, e.g., crypto::rng for crypto",
+        );
+        opts.optopt(
+            "",
+            "categories",
+            "warn about audit categories missing from the manifest and the declared ones",
+            "FILE",
+        );
+        opts.optopt(
+            "",
+            "prune-list",
+            "do not analyze calls made by the functions listed in the file",
+            "FILE",
+        );
+        opts.optflag(
+            "",
+            "audit-lang-items",
+            "analyze calls made by language items, panic machinery and formatting",
+        );
+        opts.optmulti(
+            "",
+            "remap-path-prefix",
+            "replace a prefix of recorded source paths, e.g., @registry/=$HOME/.cargo/registry/src/<index>/",
+            "FROM=TO",
+        );
+        opts.optflag(
+            "",
+            "coverage",
+            "print how many sensitive functions are reachable and audited",
+        );
+
         let matches = match opts.parse(&cmd_args[1..]) {
             Ok(m) => m,
             Err(f) => panic!(f.to_string()),
         };
 
+        let parse_count = |name: &str, default: usize| match matches.opt_str(name) {
+            Some(n) => n
+                .parse::<usize>()
+                .unwrap_or_else(|_| panic!("--{} expects a number", name)),
+            None => default,
+        };
+
+        let mut audit_options = analyzer::AuditOptions::default();
+        audit_options.panic_paths = matches.opt_present("panics");
+        if matches.opt_present("hierarchical-categories") {
+            audit_options.category_matching = analyzer::CategoryMatching::Hierarchical;
+        }
+        if let Some(edge_kinds) = matches.opt_str("edge-kinds") {
+            audit_options.edge_kinds = edge_kinds
+                .split(',')
+                .map(|kind| kind.trim().parse().unwrap_or_else(|e: String| panic!(e)))
+                .collect();
+        }
+        if matches.opt_present("all-paths") {
+            audit_options.path_mode = analyzer::PathMode::AllPaths {
+                max_depth: parse_count("max-depth", 32),
+                max_paths: parse_count("max-paths", 1000),
+            };
+        }
+
+        // taurus diff <old-store> <new-store>
+        if matches.free.first().map(String::as_str) == Some("diff") {
+            if matches.free.len() != 3 {
+                panic!("usage: taurus diff <old-store> <new-store>");
+            }
+            let stores: Vec<_> = matches.free[1..]
+                .iter()
+                .map(|store| {
+                    let store_path = Path::new(store);
+                    if !store_path.exists() {
+                        panic!("depstore {} does not exist", store);
+                    }
+                    analyzer::TaurusAnalyzer::new(store_path)
+                })
+                .collect();
+            print!("{}", diff::StoreDiff::compute(&stores[0], &stores[1]));
+            std::process::exit(rustc_driver::EXIT_SUCCESS);
+        }
+
+        let export_options = export::ExportOptions {
+            between: matches.opt_present("between"),
+            from: matches.opt_strs("from"),
+            to: matches.opt_strs("to"),
+            focus: matches
+                .opt_str("focus")
+                .map(|focus| (focus, parse_count("depth", 2))),
+            collapse: match matches.opt_str("collapse").as_ref().map(String::as_str) {
+                None => export::Collapse::Function,
+                Some("module") => export::Collapse::Module,
+                Some("crate") => export::Collapse::Crate,
+                Some(other) => panic!("cannot collapse functions into {}", other),
+            },
+            edge_labels: matches.opt_present("edge-labels"),
+        };
+
         let db_path = Path::new("target/debug/deps/taurus.depstore");
-        let analyzer = analyzer::TaurusAnalyzer::new(&db_path);
+        let mut analyzer = analyzer::TaurusAnalyzer::new(&db_path);
+        if matches.opt_present("audit-lang-items") {
+            analyzer.set_prune_list(prune::PruneList::empty());
+        } else if let Some(prune_path) = matches.opt_str("prune-list") {
+            analyzer.set_prune_list(
+                prune::PruneList::load(Path::new(&prune_path))
+                    .unwrap_or_else(|e| panic!("failed to read prune list {}: {}", prune_path, e)),
+            );
+        }
+        if let Some(manifest) = matches.opt_str("categories") {
+            analyzer.set_category_manifest(
+                analyzer::load_category_manifest(Path::new(&manifest))
+                    .unwrap_or_else(|e| panic!(e)),
+            );
+        }
+        let mut path_remapping = paths::PathRemapping::default();
+        for mapping in matches.opt_strs("remap-path-prefix") {
+            path_remapping
+                .add(&mapping)
+                .unwrap_or_else(|e| panic!("--remap-path-prefix {}", e));
+        }
+        analyzer.set_path_remapping(path_remapping);
+
+        let run_audit = || {
+            if matches.opt_present("alloc") {
+                analyzer.audit_allocations(&audit_options)
+            } else {
+                analyzer.audit(&audit_options)
+            }
+        };
+
+        // taurus query [<query>]. Queries are read from stdin if not given
+        if matches.free.first().map(String::as_str) == Some("query") {
+            let engine = query::QueryEngine::new(&analyzer);
+            let run = |line: &str| match query::Query::parse(line) {
+                Ok(q) => {
+                    let result = engine.eval(&q);
+                    if matches.opt_present("json") {
+                        println!("{}", serde_json::to_string_pretty(&result).unwrap());
+                    } else {
+                        print!("{}", result);
+                    }
+                }
+                Err(e) => eprintln!("error: {}", e),
+            };
+
+            if matches.free.len() > 1 {
+                run(&matches.free[1..].join(" "));
+            } else {
+                let stdin = std::io::stdin();
+                for line in stdin.lock().lines() {
+                    let line = line.expect("failed to read query");
+                    if !line.trim().is_empty() {
+                        run(&line);
+                    }
+                }
+            }
+            std::process::exit(rustc_driver::EXIT_SUCCESS);
+        }
 
         if matches.opt_present("d") {
-            println!("{}", analyzer.get_depgraph_dot());
+            print!("{}", analyzer.get_depgraph_dot(&export_options));
+        } else if let Some(format) = matches.opt_str("export") {
+            let graph = export::ExportGraph::new(&analyzer, &export_options);
+            match format.as_str() {
+                "dot" => print!("{}", graph.to_dot(export_options.edge_labels)),
+                "graphml" => print!("{}", graph.to_graphml()),
+                "json" => println!("{}", graph.to_json()),
+                "mermaid" => print!("{}", graph.to_mermaid(export_options.edge_labels)),
+                other => panic!("unknown export format {}", other),
+            }
+        } else if matches.opt_present("callers") {
+            print!("{}", analyzer.sink_callers());
+        } else if matches.opt_present("coverage") {
+            let coverage = coverage::Coverage::compute(&analyzer, &run_audit());
+            if matches.opt_present("json") {
+                println!("{}", serde_json::to_string_pretty(&coverage).unwrap());
+            } else {
+                print!("{}", coverage);
+            }
+        } else if let Some(baseline_path) = matches.opt_str("write-baseline") {
+            let baseline = baseline::Baseline::from_report(&run_audit());
+            baseline
+                .save(Path::new(&baseline_path))
+                .unwrap_or_else(|e| panic!("failed to write baseline {}: {}", baseline_path, e));
+            println!("recorded {} findings in {}", baseline.len(), baseline_path);
         } else {
-            analyzer.audit().emit();
+            let mut report = run_audit();
+            if let Some(baseline_path) = matches.opt_str("baseline") {
+                baseline::Baseline::load(Path::new(&baseline_path))
+                    .unwrap_or_else(|e| panic!("failed to read baseline {}: {}", baseline_path, e))
+                    .apply(&mut report);
+            }
+            match matches.opt_str("html") {
+                Some(html_dir) => html::write_html_report(&report, Path::new(&html_dir))
+                    .unwrap_or_else(|e| panic!("failed to write report to {}: {}", html_dir, e)),
+                None => report.emit(),
+            }
         }
 
         std::process::exit(rustc_driver::EXIT_SUCCESS);
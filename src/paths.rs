@@ -0,0 +1,224 @@
+// Source paths recorded in the depstore. rustc names files as it found them,
+// which yields absolute paths of the build machine and paths into the Cargo
+// registry. To keep reports and baselines portable, files of the workspace are
+// recorded relative to its root, and files of dependencies under a prefix
+// naming the crate and its version:
+//
+//   @registry/<crate>-<version>/<path>   crates from a registry
+//   @git/<repository>/<revision>/<path>  crates from git repositories
+//   @rust/<crate>-<toolchain>/<path>     the standard library
+//
+// The analyzer maps prefixes back to local directories on request, e.g., to
+// show code snippets of dependencies.
+
+use std::path::{Component, Path};
+
+pub const REGISTRY_PREFIX: &str = "@registry";
+pub const GIT_PREFIX: &str = "@git";
+pub const RUST_PREFIX: &str = "@rust";
+
+// Resolve `.` and `..` without touching the file system, since the files of
+// the standard library do not exist on the build machine
+fn clean(path: &Path) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match ret.last().map(String::as_str) {
+                Some("..") | Some("/") | None => ret.push("..".to_string()),
+                Some(_) => {
+                    ret.pop();
+                }
+            },
+            Component::RootDir => ret.push("/".to_string()),
+            Component::Prefix(prefix) => ret.push(prefix.as_os_str().to_string_lossy().into()),
+            Component::Normal(name) => ret.push(name.to_string_lossy().into()),
+        }
+    }
+    ret
+}
+
+fn join(components: &[String]) -> String {
+    match components.split_first() {
+        Some((root, rest)) if root == "/" => format!("/{}", rest.join("/")),
+        _ => components.join("/"),
+    }
+}
+
+// The position after the first occurrence of `pattern` in `components`
+fn find(components: &[String], pattern: &[&str]) -> Option<usize> {
+    components
+        .windows(pattern.len())
+        .position(|window| window.iter().zip(pattern).all(|(c, p)| c == p))
+        .map(|pos| pos + pattern.len())
+}
+
+pub struct PathNormalizer {
+    workspace_root: Vec<String>,
+    // The directory relative paths given to rustc start from
+    working_dir: Vec<String>,
+    // The toolchain of the compiler, such as `nightly-2019-11-25`
+    rustc_version: String,
+}
+
+impl PathNormalizer {
+    pub fn new(workspace_root: &Path, working_dir: &Path, rustc_version: &str) -> Self {
+        Self {
+            workspace_root: clean(workspace_root),
+            working_dir: clean(working_dir),
+            rustc_version: rustc_version.to_string(),
+        }
+    }
+
+    /// The portable name of a source file
+    pub fn normalize(&self, path: &Path) -> String {
+        let components = if path.is_absolute() {
+            clean(path)
+        } else {
+            let mut components = self.working_dir.clone();
+            components.extend(clean(path));
+            clean(Path::new(&join(&components)))
+        };
+
+        // The standard library is built with its sources remapped to
+        // `/rustc/<commit>/src`, and rust-src installs them into the sysroot
+        let std_src = match components.first().map(String::as_str) {
+            Some("/") if components.get(1).map(String::as_str) == Some("rustc") => Some(4),
+            _ => find(&components, &["rustlib", "src", "rust", "src"]),
+        };
+        if let Some(pos) = std_src {
+            if let Some(krate) = components.get(pos).filter(|c| c.starts_with("lib")) {
+                return format!(
+                    "{}/{}-{}/{}",
+                    RUST_PREFIX,
+                    &krate[3..],
+                    self.rustc_version,
+                    components[pos + 1..].join("/")
+                );
+            }
+        }
+
+        // registry/src/<index>/<crate>-<version>, where the index directory
+        // depends on the registry URL and is left out
+        if let Some(index) = find(&components, &["registry", "src"]) {
+            let krate = index + 1;
+            if components.len() > krate + 1 {
+                return format!("{}/{}", REGISTRY_PREFIX, components[krate..].join("/"));
+            }
+        }
+
+        // git/checkouts/<repository>/<revision>
+        if let Some(pos) = find(&components, &["git", "checkouts"]) {
+            if components.len() > pos + 2 {
+                return format!("{}/{}", GIT_PREFIX, components[pos..].join("/"));
+            }
+        }
+
+        if components.starts_with(&self.workspace_root) {
+            return components[self.workspace_root.len()..].join("/");
+        }
+
+        join(&components)
+    }
+}
+
+/// Prefixes of recorded paths replaced by the analyzer, like
+/// `--remap-path-prefix` of rustc. The last matching prefix wins
+#[derive(Default)]
+pub struct PathRemapping {
+    mapping: Vec<(String, String)>,
+}
+
+impl PathRemapping {
+    /// Add a mapping given as `FROM=TO`
+    pub fn add(&mut self, arg: &str) -> Result<(), String> {
+        match arg.rfind('=') {
+            Some(pos) => {
+                let from = arg[..pos].trim_end_matches('/');
+                let to = arg[pos + 1..].trim_end_matches('/');
+                self.mapping.push((from.to_string(), to.to_string()));
+                Ok(())
+            }
+            None => Err(format!("expected FROM=TO, found {}", arg)),
+        }
+    }
+
+    pub fn remap(&self, file: &str) -> String {
+        for (from, to) in self.mapping.iter().rev() {
+            if file == from {
+                return to.clone();
+            }
+            if file.starts_with(from.as_str()) && file[from.len()..].starts_with('/') {
+                return format!("{}{}", to, &file[from.len()..]);
+            }
+        }
+
+        file.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PathNormalizer;
+    use std::path::Path;
+
+    fn normalizer() -> PathNormalizer {
+        PathNormalizer::new(
+            Path::new("/home/user/enclave"),
+            Path::new("/home/user/enclave/app"),
+            "nightly-2019-11-25",
+        )
+    }
+
+    #[test]
+    fn normalize_registry() {
+        assert_eq!(
+            normalizer().normalize(Path::new(
+                "/home/user/.cargo/registry/src/github.com-1ecc6299db9ec823/libc-0.2.66/src/lib.rs"
+            )),
+            "@registry/libc-0.2.66/src/lib.rs"
+        );
+    }
+
+    #[test]
+    fn normalize_git() {
+        assert_eq!(
+            normalizer().normalize(Path::new(
+                "/home/user/.cargo/git/checkouts/sgx-world-3c4e5f/a1b2c3d/sgx_tstd/src/lib.rs"
+            )),
+            "@git/sgx-world-3c4e5f/a1b2c3d/sgx_tstd/src/lib.rs"
+        );
+    }
+
+    #[test]
+    fn normalize_std() {
+        let normalizer = normalizer();
+        assert_eq!(
+            normalizer.normalize(Path::new("/rustc/a1b2c3d/src/libcore/ptr/mod.rs")),
+            "@rust/core-nightly-2019-11-25/ptr/mod.rs"
+        );
+        assert_eq!(
+            normalizer.normalize(Path::new(
+                "/home/user/.rustup/toolchains/nightly/lib/rustlib/src/rust/src/liballoc/vec.rs"
+            )),
+            "@rust/alloc-nightly-2019-11-25/vec.rs"
+        );
+    }
+
+    #[test]
+    fn normalize_workspace() {
+        let normalizer = normalizer();
+        assert_eq!(
+            normalizer.normalize(Path::new("src/main.rs")),
+            "app/src/main.rs"
+        );
+        assert_eq!(
+            normalizer.normalize(Path::new("../lib/./src/lib.rs")),
+            "lib/src/lib.rs"
+        );
+        assert_eq!(
+            normalizer.normalize(Path::new("/tmp/gen.rs")),
+            "/tmp/gen.rs"
+        );
+    }
+}
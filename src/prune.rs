@@ -0,0 +1,102 @@
+// The functions whose callees are not analyzed. Language items, the panic
+// machinery and formatting are reached from almost everywhere and mostly call
+// into each other, so following them buries real findings in noise. Calls made
+// from a pruned function are removed from the dependency graph, but the
+// function itself and whatever else it is called from are kept.
+//
+// A prune list is written one rule per line, as `<reason> <pattern>` where the
+// pattern is a function path as accepted by queries. The line `lang_items`
+// prunes the functions the extractor identified as language items.
+
+use std::io::{BufRead, BufReader, Error, ErrorKind};
+use std::path::Path;
+
+use crate::query::matches_pattern;
+
+pub const LANG_ITEM_REASON: &str = "lang_item";
+
+const DEFAULT_RULES: &[(&str, &str)] = &[
+    ("panic", "core::panicking::*"),
+    ("panic", "std::panicking::*"),
+    ("panic", "std::rt::begin_panic*"),
+    ("fmt", "core::fmt::*"),
+    ("fmt", "alloc::fmt::*"),
+    ("fmt", "std::fmt::*"),
+];
+
+pub struct PruneRule {
+    pub reason: String,
+    pub pattern: String,
+}
+
+pub struct PruneList {
+    pub lang_items: bool,
+    pub rules: Vec<PruneRule>,
+}
+
+impl Default for PruneList {
+    fn default() -> Self {
+        Self {
+            lang_items: true,
+            rules: DEFAULT_RULES
+                .iter()
+                .map(|&(reason, pattern)| PruneRule {
+                    reason: reason.to_string(),
+                    pattern: pattern.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl PruneList {
+    /// A list pruning nothing, such that every call is audited
+    pub fn empty() -> Self {
+        Self {
+            lang_items: false,
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let mut ret = Self::empty();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["lang_items"] => ret.lang_items = true,
+                [reason, pattern] => ret.rules.push(PruneRule {
+                    reason: reason.to_string(),
+                    pattern: pattern.to_string(),
+                }),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("malformed prune rule: {}", line),
+                    ))
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// The reason for pruning the callees of a function, if they are pruned
+    pub fn reason(&self, name: &str, is_lang_item: bool) -> Option<&str> {
+        if self.lang_items && is_lang_item {
+            return Some(LANG_ITEM_REASON);
+        }
+
+        self.rules
+            .iter()
+            .find(|rule| matches_pattern(name, &rule.pattern))
+            .map(|rule| rule.reason.as_str())
+    }
+}
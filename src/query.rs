@@ -0,0 +1,301 @@
+// Ad-hoc queries over the dependency graph. A query consists of a command,
+// its arguments, and optional filters on the functions in the result:
+//
+//   callers <fn> [filters]           direct callers of <fn>
+//   callees <fn> [filters]           direct callees of <fn>
+//   sinks <fn> [filters]             sensitive functions reachable from <fn>
+//   reachable <from> <to> [filters]  whether <to> is reachable from <from>
+//   path <from> <to> [filters]       the shortest path from <from> to <to>
+//
+// Functions are written as paths without disambiguators and type parameters,
+// e.g., `mycrate::module::func`. A path matches all functions ending with it,
+// and a trailing `*` matches all functions under a prefix. Filters are written
+// as `crate=<name>`, `module=<path>`, and `category=<name>`. A category filter
+// also matches the subcategories of the category. Filters of `reachable` and
+// `path` restrict the functions matching <to>.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use petgraph::stable_graph::{EdgeReference, NodeIndex};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+
+use crate::analyzer::{category_ancestors, DepGraph, DepPath, EdgeWeight, TaurusAnalyzer};
+use crate::summaries::{EdgeKind, SourceLocation};
+
+/// The name of a function without disambiguators and type parameters
+pub fn display_name(name: &str) -> String {
+    let name = name.find('<').map_or(name, |pos| &name[..pos]);
+    let mut ret = String::new();
+    let mut in_brackets = false;
+
+    for c in name.chars() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            c if !in_brackets => ret.push(c),
+            _ => (),
+        }
+    }
+
+    ret
+}
+
+/// The name of the crate defining a function
+pub fn crate_name(name: &str) -> String {
+    display_name(name)
+        .split("::")
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+pub fn matches_pattern(name: &str, pattern: &str) -> bool {
+    let name = display_name(name);
+    if pattern.ends_with('*') {
+        name.starts_with(&pattern[..pattern.len() - 1])
+    } else {
+        name == pattern || name.ends_with(&format!("::{}", pattern))
+    }
+}
+
+enum Command {
+    Callers(String),
+    Callees(String),
+    Sinks(String),
+    Reachable(String, String),
+    Path(String, String),
+}
+
+enum Filter {
+    Crate(String),
+    Module(String),
+    Category(String),
+}
+
+pub struct Query {
+    command: Command,
+    filters: Vec<Filter>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let mut words = Vec::new();
+        let mut filters = Vec::new();
+
+        for word in query.split_whitespace() {
+            if let Some(pos) = word.find('=') {
+                let value = word[pos + 1..].to_string();
+                filters.push(match &word[..pos] {
+                    "crate" => Filter::Crate(value),
+                    "module" => Filter::Module(value),
+                    "category" => Filter::Category(value),
+                    other => return Err(format!("unknown filter {}", other)),
+                });
+            } else {
+                words.push(word.to_string());
+            }
+        }
+
+        let command = match (words.first().map(String::as_str), words.len()) {
+            (Some("callers"), 2) => Command::Callers(words[1].clone()),
+            (Some("callees"), 2) => Command::Callees(words[1].clone()),
+            (Some("sinks"), 2) => Command::Sinks(words[1].clone()),
+            (Some("reachable"), 3) => Command::Reachable(words[1].clone(), words[2].clone()),
+            (Some("path"), 3) => Command::Path(words[1].clone(), words[2].clone()),
+            (Some(command), _) => return Err(format!("malformed {} query", command)),
+            (None, _) => return Err("empty query".to_string()),
+        };
+
+        Ok(Self { command, filters })
+    }
+}
+
+#[derive(Serialize)]
+pub struct Call {
+    pub caller: String,
+    pub callee: String,
+    pub src_loc: SourceLocation,
+    pub kind: EdgeKind,
+}
+
+#[derive(Serialize)]
+pub struct Sink {
+    pub name: String,
+    pub category: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QueryResult {
+    Calls { calls: Vec<Call> },
+    Sinks { sinks: Vec<Sink> },
+    Reachable { reachable: bool },
+    Path { path: Option<DepPath> },
+}
+
+impl std::fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            QueryResult::Calls { calls } => {
+                for call in calls {
+                    write!(
+                        f,
+                        "{} -> {} at {} [{}]\n",
+                        call.caller, call.callee, call.src_loc, call.kind
+                    )?;
+                }
+            }
+            QueryResult::Sinks { sinks } => {
+                for sink in sinks {
+                    write!(f, "{} ({})\n", sink.name, sink.category)?;
+                }
+            }
+            QueryResult::Reachable { reachable } => write!(f, "{}\n", reachable)?,
+            QueryResult::Path { path: Some(path) } => write!(f, "{}", path)?,
+            QueryResult::Path { path: None } => write!(f, "no path\n")?,
+        }
+
+        Ok(())
+    }
+}
+
+pub struct QueryEngine<'a> {
+    analyzer: &'a TaurusAnalyzer,
+    dg: DepGraph,
+}
+
+impl<'a> QueryEngine<'a> {
+    pub fn new(analyzer: &'a TaurusAnalyzer) -> Self {
+        Self {
+            analyzer,
+            dg: analyzer.get_depgraph().0,
+        }
+    }
+
+    fn category(&self, node: NodeIndex) -> Option<String> {
+        self.analyzer
+            .get_marking(self.dg.node_weight(node).unwrap())
+            .and_then(|marking| marking.require_audit)
+    }
+
+    fn passes_filters(&self, node: NodeIndex, filters: &[Filter]) -> bool {
+        let name = display_name(self.dg.node_weight(node).unwrap());
+        filters.iter().all(|filter| match filter {
+            Filter::Crate(krate) => crate_name(&name) == *krate,
+            Filter::Module(module) => name.starts_with(&format!("{}::", module)),
+            Filter::Category(category) => self.category(node).map_or(false, |sink_category| {
+                category_ancestors(&sink_category).contains(&category.as_str())
+            }),
+        })
+    }
+
+    fn find(&self, pattern: &str) -> Vec<NodeIndex> {
+        self.dg
+            .node_indices()
+            .filter(|&idx| matches_pattern(self.dg.node_weight(idx).unwrap(), pattern))
+            .collect()
+    }
+
+    fn find_filtered(&self, pattern: &str, filters: &[Filter]) -> Vec<NodeIndex> {
+        self.find(pattern)
+            .into_iter()
+            .filter(|&node| self.passes_filters(node, filters))
+            .collect()
+    }
+
+    fn calls(&self, pattern: &str, direction: Direction, filters: &[Filter]) -> QueryResult {
+        let mut calls = Vec::new();
+
+        for node in self.find(pattern) {
+            for edge in self.dg.edges_directed(node, direction) {
+                let other = match direction {
+                    Direction::Incoming => edge.source(),
+                    Direction::Outgoing => edge.target(),
+                };
+                if self.passes_filters(other, filters) {
+                    calls.push(Call {
+                        caller: self.dg.node_weight(edge.source()).unwrap().to_string(),
+                        callee: self.dg.node_weight(edge.target()).unwrap().to_string(),
+                        src_loc: edge.weight().src_loc.clone(),
+                        kind: edge.weight().kind,
+                    });
+                }
+            }
+        }
+
+        QueryResult::Calls { calls }
+    }
+
+    // Breadth-first search from all functions matching `pattern`, returning
+    // the edges through which nodes are first reached
+    fn search(&self, pattern: &str) -> HashMap<NodeIndex, Option<EdgeReference<EdgeWeight>>> {
+        let mut pred = HashMap::new();
+        let mut queue = VecDeque::new();
+
+        for node in self.find(pattern) {
+            pred.insert(node, None);
+            queue.push_back(node);
+        }
+
+        while let Some(node) = queue.pop_front() {
+            for edge in self.dg.edges(node) {
+                if !pred.contains_key(&edge.target()) {
+                    pred.insert(edge.target(), Some(edge));
+                    queue.push_back(edge.target());
+                }
+            }
+        }
+
+        pred
+    }
+
+    pub fn eval(&self, query: &Query) -> QueryResult {
+        match &query.command {
+            Command::Callers(pattern) => self.calls(pattern, Direction::Incoming, &query.filters),
+            Command::Callees(pattern) => self.calls(pattern, Direction::Outgoing, &query.filters),
+            Command::Sinks(pattern) => {
+                let mut sinks: Vec<Sink> = self
+                    .search(pattern)
+                    .keys()
+                    .filter(|&&node| self.passes_filters(node, &query.filters))
+                    .filter_map(|&node| {
+                        self.category(node).map(|category| Sink {
+                            name: self.dg.node_weight(node).unwrap().to_string(),
+                            category,
+                        })
+                    })
+                    .collect();
+                sinks.sort_by(|a, b| a.name.cmp(&b.name));
+                QueryResult::Sinks { sinks }
+            }
+            Command::Reachable(from, to) => {
+                let targets: HashSet<NodeIndex> =
+                    self.find_filtered(to, &query.filters).into_iter().collect();
+                QueryResult::Reachable {
+                    reachable: self.search(from).keys().any(|node| targets.contains(node)),
+                }
+            }
+            Command::Path(from, to) => {
+                let pred = self.search(from);
+                let path = self
+                    .find_filtered(to, &query.filters)
+                    .into_iter()
+                    .filter(|node| pred.contains_key(node))
+                    .map(|target| {
+                        let mut edges = Vec::new();
+                        let mut current = target;
+                        while let Some(Some(edge)) = pred.get(&current) {
+                            edges.push(*edge);
+                            current = edge.source();
+                        }
+                        edges.reverse();
+                        edges
+                    })
+                    .min_by_key(|edges| edges.len())
+                    .map(|edges| DepPath::instantiate(&edges, &self.dg));
+                QueryResult::Path { path }
+            }
+        }
+    }
+}
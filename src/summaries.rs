@@ -1,8 +1,8 @@
 use rustc::hir::def_id::DefId;
 use rustc::ty::subst::SubstsRef;
-use rustc::ty::{Ty, TyCtxt};
+use rustc::ty::{AssocKind, Ty, TyCtxt};
 use syntax::source_map::SourceMap;
-use syntax_pos::Loc;
+use syntax_pos::{FileName, Pos, Span};
 
 #[cfg(feature = "use_sqlite")]
 use rusqlite::types::ToSql;
@@ -22,9 +22,49 @@ extern crate serde;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use crate::paths::PathNormalizer;
 use crate::utils::*;
 
 pub const FNPTR_DEF_NAME_CANONICAL: &'static str = "@fnptr";
+// Pseudo functions standing for the sites where a panic may start
+pub const PANIC_DEF_NAME_CANONICAL: &'static str = "@panic";
+// Category of panic sites, which must not unwind across entry points
+pub const PANIC_CATEGORY: &'static str = "panic";
+// Category of heap allocations, reported for #[taurus::no_alloc] functions
+pub const ALLOC_CATEGORY: &'static str = "alloc";
+// Category of mutable statics, i.e., state shared by the whole enclave
+pub const GLOBAL_STATE_CATEGORY: &'static str = "global_state";
+// Pseudo functions standing for inline assembly and calls to intrinsics
+pub const ASM_DEF_NAME_CANONICAL: &'static str = "@asm";
+pub const INTRINSIC_DEF_NAME_CANONICAL: &'static str = "@intrinsic";
+pub const ASM_CATEGORY: &'static str = "asm";
+pub const INTRINSIC_CATEGORY: &'static str = "intrinsic";
+// Subcategories of intrinsics accessing memory as volatile, using SIMD
+// instructions, reading the hardware random number generator and calling into
+// LLVM. Other intrinsics, such as `size_of` and `transmute`, are not sensitive
+pub const INTRINSIC_VOLATILE_CATEGORY: &'static str = "intrinsic::volatile";
+pub const INTRINSIC_SIMD_CATEGORY: &'static str = "intrinsic::simd";
+pub const INTRINSIC_RDRAND_CATEGORY: &'static str = "intrinsic::rdrand";
+pub const INTRINSIC_LLVM_CATEGORY: &'static str = "intrinsic::llvm";
+
+// Categories known without being declared
+pub const BUILTIN_CATEGORIES: &[&str] = &[
+    OCALL_CATEGORY,
+    USER_CHECK_CATEGORY,
+    PANIC_CATEGORY,
+    ALLOC_CATEGORY,
+    GLOBAL_STATE_CATEGORY,
+    ASM_CATEGORY,
+    INTRINSIC_CATEGORY,
+    INTRINSIC_VOLATILE_CATEGORY,
+    INTRINSIC_SIMD_CATEGORY,
+    INTRINSIC_RDRAND_CATEGORY,
+    INTRINSIC_LLVM_CATEGORY,
+];
+// Category of the OCALLs declared in EDL files
+pub const OCALL_CATEGORY: &'static str = "ocall";
+// Category waiving the [user_check] parameters of ECALLs
+pub const USER_CHECK_CATEGORY: &'static str = "user_check";
 
 pub struct Canonical<'tcx, 'rtcx>
 where
@@ -67,30 +107,110 @@ impl<'tcx, 'rtcx> Canonical<'tcx, 'rtcx> {
     pub fn def_name(&self, def_id: DefId) -> String {
         qualified_type_name(self.tcx, def_id)
     }
+
+    /// If `def_id` is a method in a trait impl, returns the name of the trait
+    /// method it implements
+    pub fn implemented_trait_item(&self, def_id: DefId) -> Option<String> {
+        let impl_did = self.tcx.impl_of_method(def_id)?;
+        let trait_did = self.tcx.trait_id_of_impl(impl_did)?;
+        let method_name = self.tcx.item_name(def_id);
+        self.tcx
+            .associated_items(trait_did)
+            .find(|item| item.kind == AssocKind::Method && item.ident.name == method_name)
+            .map(|item| self.def_name(item.def_id))
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct SourceLocation {
     pub file: String,
     pub line_no: usize,
+    // Columns count characters from 1, and the end is exclusive
+    pub col_no: usize,
+    pub end_line_no: usize,
+    pub end_col_no: usize,
+}
+
+impl SourceLocation {
+    pub fn from_span(source_map: &SourceMap, span: Span, paths: &PathNormalizer) -> Self {
+        let lo = source_map.lookup_char_pos(span.lo());
+        let hi = source_map.lookup_char_pos(span.hi());
+        Self {
+            // Other names stand for macro expansions and the like
+            file: match &lo.file.name {
+                FileName::Real(path) => paths.normalize(path),
+                name => name.to_string(),
+            },
+            line_no: lo.line,
+            col_no: lo.col.to_usize() + 1,
+            end_line_no: hi.line,
+            end_col_no: hi.col.to_usize() + 1,
+        }
+    }
 }
 
 impl std::fmt::Display for SourceLocation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}", self.file, self.line_no)
+        write!(f, "{}:{}:{}", self.file, self.line_no, self.col_no)
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Serialize, Deserialize, Debug)]
+#[derive(PartialEq, Eq, Clone, Default, Serialize, Deserialize, Debug)]
 pub struct Marking {
     pub require_audit: Option<String>,
     pub audited: Option<String>,
     pub is_entry_point: bool,
+    // Instances of a generic entry point to be audited. `None` means all
+    // instances found in the dependency graph
+    pub entry_instances: Option<Vec<String>>,
+    // Parameters of an ECALL declared [user_check] in EDL files
+    pub user_check_params: Vec<String>,
+    // The function must not reach heap allocations
+    pub no_alloc: bool,
 }
 
 impl Marking {
+    /// The marking of a sensitive function of the given category
+    pub fn sink(category: &str) -> Self {
+        Self {
+            require_audit: Some(category.to_string()),
+            ..Self::default()
+        }
+    }
+
     pub fn annotated(&self) -> bool {
-        self.is_entry_point || self.require_audit.is_some() || self.audited.is_some()
+        self.is_entry_point
+            || self.require_audit.is_some()
+            || self.audited.is_some()
+            || self.no_alloc
+    }
+}
+
+impl std::fmt::Display for Marking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut attrs = Vec::new();
+        if let Some(meta) = &self.require_audit {
+            attrs.push(format!("require_audit(\"{}\")", meta));
+        }
+        if let Some(meta) = &self.audited {
+            attrs.push(format!("audited(\"{}\")", meta));
+        }
+        if self.is_entry_point {
+            match &self.entry_instances {
+                Some(instances) => attrs.push(format!(
+                    "entry_point(instances = \"{}\")",
+                    instances.join(", ")
+                )),
+                None => attrs.push("entry_point".to_string()),
+            }
+        }
+        if !self.user_check_params.is_empty() {
+            attrs.push(format!("user_check({})", self.user_check_params.join(", ")));
+        }
+        if self.no_alloc {
+            attrs.push("no_alloc".to_string());
+        }
+        write!(f, "[{}]", attrs.join(", "))
     }
 }
 
@@ -100,12 +220,73 @@ pub struct MarkedItem {
     pub src_loc: SourceLocation,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    // Statically dispatched calls, and functions used as values
+    Call,
+    // Calls through trait objects. The callee is the trait method
+    Virtual,
+    // Calls through function pointers. The callee stands for the call site
+    #[serde(rename = "fnptr")]
+    FnPtr,
+    // Destructors run when values are dropped
+    Drop,
+    // Reads and writes of statics. The callee is the static
+    Static,
+    // Calls of closures
+    Closure,
+}
+
+impl EdgeKind {
+    pub const ALL: &'static [EdgeKind] = &[
+        EdgeKind::Call,
+        EdgeKind::Virtual,
+        EdgeKind::FnPtr,
+        EdgeKind::Drop,
+        EdgeKind::Static,
+        EdgeKind::Closure,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EdgeKind::Call => "call",
+            EdgeKind::Virtual => "virtual",
+            EdgeKind::FnPtr => "fnptr",
+            EdgeKind::Drop => "drop",
+            EdgeKind::Static => "static",
+            EdgeKind::Closure => "closure",
+        }
+    }
+}
+
+impl std::str::FromStr for EdgeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EdgeKind::ALL
+            .iter()
+            .find(|kind| kind.as_str() == s)
+            .cloned()
+            .ok_or_else(|| format!("unknown edge kind {}", s))
+    }
+}
+
+impl std::fmt::Display for EdgeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DepEdge {
     pub callee_def: String,
+    // The trait method implemented by the callee, if there is any
+    pub trait_item: Option<String>,
     pub is_lang_item: bool,
     pub type_params: Vec<String>,
     pub src_loc: SourceLocation,
+    pub kind: EdgeKind,
 }
 
 impl DepEdge {
@@ -121,15 +302,6 @@ impl DepEdge {
     }
 }
 
-impl From<&Loc> for SourceLocation {
-    fn from(loc: &Loc) -> Self {
-        Self {
-            file: loc.file.name.to_string(),
-            line_no: loc.line,
-        }
-    }
-}
-
 #[cfg(feature = "use_sqlite")]
 pub struct PersistentSummaryStore<V>
 where